@@ -0,0 +1,12 @@
+/**
+ * Headless balance simulator, see `simulate.rs` in the library.
+ * Bots play through the same game state as the GUI, so they play by exactly its rules.
+ */
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if let Err(message) = schack_gui::simulate::run(&args) {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,6008 @@
+/**
+ * Chess GUI template: the game state, the rules and the window event handling,
+ * shared by the GUI in `main.rs` and the headless simulator in `bin/simulate.rs`.
+ * Author: Eskil Queseth <eskilq@kth.se>, Viola Söderlund <violaso@kth.se>
+ */
+
+use ggez::event;
+use ggez::timer;
+use ggez::graphics::{self, DrawParam, Color, DrawMode};
+use ggez::{Context, GameResult};
+use eliasfl_chess::{Game, GameState, Color as Colour, Piece as PieceType, Position};
+use ggez::event::{MouseButton, KeyCode, KeyMods};
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use rand::Rng;
+use rand::rngs;
+
+pub mod simulate;
+
+static mut seed: u64 = 0;
+
+/// A chess board is 8x8 tiles.
+const GRID_SIZE: (i16, i16) = (8, 8);
+/// Sutible size of each tile.
+const GRID_CELL_SIZE: (i16, i16) = (45, 45);
+
+/// Size of the application window.
+pub const SCREEN_SIZE: (f32, f32) = (
+    GRID_SIZE.0 as f32 * GRID_CELL_SIZE.0 as f32 * 3.0,
+    GRID_SIZE.1 as f32 * GRID_CELL_SIZE.1 as f32 * 1.5,
+);
+
+// GUI Color representations
+const BLACK: Color = Color::new(228.0/255.0, 196.0/255.0, 108.0/255.0, 1.0);
+const WHITE: Color = Color::new(188.0/255.0, 140.0/255.0, 76.0/255.0, 1.0);
+const BLACK_RED: Color = Color::new(255.0/255.0, 96.0/255.0, 78.0/255.0, 1.0);
+const WHITE_RED: Color = Color::new(215.0/255.0, 69.0/255.0, 60.0/255.0, 1.0);
+const SEMI_OPAQUE_GREY: Color = Color::new(0.5, 0.5, 0.5, 0.2);
+
+/// Standard starting position, used to tell when a game needs SetUp/FEN tags.
+const STANDARD_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+/// Number of mods listed on each page of the help screen.
+const HELP_PER_PAGE: usize = 6;
+
+/// Number of times each player may reroll their mod offers during a series.
+const STARTING_REROLLS: u8 = 2;
+/// Number of mods each player may banish during a series.
+const MAX_BANISHES: usize = 3;
+/// Rolls tried for a mod offer before the slot is left empty.
+const OFFER_TRIES: usize = 200;
+/// Height of each of the three offer boxes on the mod screen.
+const MOD_BOX_HEIGHT: f32 = (SCREEN_SIZE.1 - GRID_CELL_SIZE.1 as f32 * 3.5) / 3.0;
+
+/// Seconds an atomic explosion stays on screen.
+const EXPLOSION_TIME: f32 = 0.6;
+/// Seconds on each bughouse clock at the start of a game.
+const BUGHOUSE_CLOCK: f32 = 300.0;
+/// Tile size of the partner board drawn next to the main one in bughouse.
+const MINI_CELL_SIZE: f32 = GRID_CELL_SIZE.0 as f32 * 0.5;
+/// Match history, one finished game per line, kept next to wherever the game is run from.
+const HISTORY_FILE: &str = "history.txt";
+/// Local player profiles, one per line, kept next to the match history.
+const PROFILES_FILE: &str = "profiles.txt";
+/// Starting placement of Racing Kings, both sides on the first two ranks.
+const RACING_KINGS_START: &str = "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ";
+/// Game saved with Ctrl+S and loaded with Ctrl+L, kept next to the match history.
+const SAVE_FILE: &str = "saved_game.txt";
+/// Position exported from the board editor as FEN, kept next to the match history.
+const POSITION_FILE: &str = "position.fen";
+/// Rating a new profile starts on.
+const START_RATING: f32 = 1200.0;
+/// Most rating points a single game can win or lose.
+const ELO_K: f32 = 32.0;
+/// Rating points a held mod is taken to be worth when mods count towards ratings.
+const MOD_ELO: f32 = 40.0;
+/// Profiles listed on the profile screen.
+const PROFILE_ROWS: usize = 12;
+
+// Enumerable over possible modifications for a player. 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Mods {
+    CrazyHouse(PieceType),
+    Atomic(PieceType),
+    Sniper(PieceType),
+    KingOfTheHill,
+    Extinction(PieceType),
+    // Hidden(PieceType), Only works with networking... will need to be rethought somewhat
+    TripleCheck(PieceType),
+    Chess960,
+    Army(usize),
+    Antichess,
+    RacingKings,
+    Duck,
+    Portal(PieceType),
+    Evolution(PieceType),
+    Fairy(Fairy),
+    Shield(PieceType),
+    DoubleMove,
+    ExtraPawn(u8),
+    SpareKnight,
+    Sabotage(PieceType),
+    Teleport,
+}
+
+/// Compound fairy pieces. On the library board they stand as the piece they are built on,
+/// and the GUI adds the knight jumps on top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Fairy {
+    Archbishop,
+    Chancellor,
+    Amazon,
+}
+
+impl Fairy {
+    fn name(&self) -> &str {
+        match self {
+            Fairy::Archbishop => "Archbishop",
+            Fairy::Chancellor => "Chancellor",
+            Fairy::Amazon => "Amazon",
+        }
+    }
+
+    /// Its name with the indefinite article, e.g. "an Amazon" or "a Chancellor".
+    fn with_article(&self) -> String {
+        match self {
+            Fairy::Chancellor => format!("a {}", self.name()),
+            _ => format!("an {}", self.name()),
+        }
+    }
+
+    /// The standard piece it replaces and stands in for on the library board.
+    fn base(&self, col: Colour) -> PieceType {
+        match self {
+            Fairy::Archbishop => PieceType::Bishop(col),
+            Fairy::Chancellor => PieceType::Rook(col),
+            Fairy::Amazon => PieceType::Queen(col),
+        }
+    }
+
+    /// Letter used for it in move notation.
+    fn letter(&self) -> char {
+        match self {
+            Fairy::Archbishop => 'A',
+            Fairy::Chancellor => 'C',
+            Fairy::Amazon => 'M',
+        }
+    }
+
+    /// Rough worth in pawns.
+    fn value(&self) -> u8 {
+        match self {
+            Fairy::Archbishop => 8,
+            Fairy::Chancellor => 9,
+            Fairy::Amazon => 12,
+        }
+    }
+
+    /// Files on the back rank it prefers to replace its base piece on.
+    fn home_files(&self) -> [u8; 2] {
+        match self {
+            Fairy::Archbishop => [3, 6],
+            Fairy::Chancellor => [1, 8],
+            Fairy::Amazon => [4, 4],
+        }
+    }
+}
+
+impl Mods {
+    fn to_string(&self) -> &str {
+        match self {
+            Mods::CrazyHouse(_) => "CrazyHouse",
+            Mods::Atomic(_) => "Atomic",
+            Mods::Sniper(_) => "Sniper",
+            Mods::KingOfTheHill => "King of the Hill",
+            Mods::Extinction(_) => "Extinction",
+            Mods::TripleCheck(_) => "Triple Check",
+            Mods::Chess960 => "Chess960",
+            Mods::Army(_) => "Army",
+            Mods::Antichess => "Antichess",
+            Mods::RacingKings => "Racing Kings",
+            Mods::Duck => "Duck Chess",
+            Mods::Portal(_) => "Portal",
+            Mods::Evolution(_) => "Evolution",
+            Mods::Fairy(_) => "Fairy",
+            Mods::Shield(_) => "Shield",
+            Mods::DoubleMove => "Double Move",
+            Mods::ExtraPawn(_) => "Extra Pawn",
+            Mods::SpareKnight => "Spare Knight",
+            Mods::Sabotage(_) => "Sabotage",
+            Mods::Teleport => "Teleport",
+        }
+    }
+
+    /// Human-readable name of the mod as held by `col`, e.g. "Sniper Knight".
+    fn display_name(&self, col: Colour) -> String {
+        match self {
+            Mods::KingOfTheHill | Mods::Chess960 | Mods::Antichess | Mods::RacingKings | Mods::Duck | Mods::DoubleMove | Mods::SpareKnight | Mods::Teleport => self.to_string().to_string(),
+            Mods::ExtraPawn(file) => format!("Extra Pawn ({}-file)", (b'a' + file - 1) as char),
+            Mods::Army(army) => format!("{} Army", armies()[*army].name),
+            Mods::Fairy(fairy) => fairy.name().to_string(),
+            _ => {
+                let piece = self.get_piece(col);
+                if piece.colour() == col {
+                    format!("{} {}", self.to_string(), piece_name(piece))
+                } else {
+                    format!("{} (enemy {})", self.to_string(), piece_name(piece))
+                }
+            }
+        }
+    }
+
+    /// Explanation of the rules the mod adds to the game.
+    fn description(&self) -> String {
+        let piece = match self {
+            Mods::CrazyHouse(piece) | Mods::Atomic(piece) | Mods::Sniper(piece) | Mods::Extinction(piece) | Mods::TripleCheck(piece) | Mods::Portal(piece) | Mods::Evolution(piece) | Mods::Shield(piece) | Mods::Sabotage(piece) => piece_name(*piece),
+            Mods::KingOfTheHill | Mods::Chess960 | Mods::Army(_) | Mods::Antichess | Mods::RacingKings | Mods::Duck | Mods::DoubleMove | Mods::Teleport => "King",
+            Mods::ExtraPawn(_) => "Pawn",
+            Mods::SpareKnight => "Knight",
+            Mods::Fairy(fairy) => piece_name(fairy.base(Colour::White)),
+        };
+        match self {
+            Mods::CrazyHouse(_) => format!("Every enemy {} you capture lands in your tray. Instead of moving, you may drop it on any empty square.", piece),
+            Mods::Atomic(_) => format!("When your {} captures, it explodes, destroying itself and every piece other than pawns on the squares around the capture. You may not blow up your own King, your King can't capture, and exploding pieces give no check while the Kings touch.", piece),
+            Mods::Sniper(_) => format!("Your {} captures from afar: the target is removed but your {} stays on its square.", piece, piece),
+            Mods::KingOfTheHill => format!("Walk your King onto one of the four centre squares (d4, e4, d5, e5) to win."),
+            Mods::Extinction(_) => format!("Capture every enemy {} to win the game.", piece),
+            Mods::TripleCheck(_) => format!("Give check with your {} three times to win.", piece),
+            Mods::Chess960 => format!("Your back rank starts shuffled into one of the 960 Fischer random setups. To castle, click your King and then the Rook: they end up on the usual castling squares."),
+            Mods::Army(army) => format!("You start with the {} army instead of the usual one. {}", armies()[*army].name, armies()[*army].description),
+            Mods::Antichess => format!("The next game is losing chess: captures are compulsory, the King is an ordinary piece and whoever runs out of pieces or moves first wins. Used up after one game."),
+            Mods::RacingKings => format!("The next game is a race: both armies start on the first two ranks, nobody may give check and the first King to reach the eighth rank wins. If Black answers by reaching it too, the game is drawn. Used up after one game."),
+            Mods::Portal(_) => format!("Two linked portals appear on the board. Your {} can step into one and come out of the other, carrying on in the same direction.", piece),
+            Mods::Fairy(fairy) => format!("One of your {}s becomes {}, which moves both like a {} and like a Knight. It is worth about {} pawns.", piece, fairy.with_article(), piece, fairy.value()),
+            Mods::Shield(_) => format!("Your {}s start the game with a shield. The first capture against each one only breaks its shield: the attacker stays on its square and the turn passes. An atomic blast or a Sniper shot also just breaks it.", piece),
+            Mods::ExtraPawn(file) => format!("You start every game with an extra {} on your third rank of the {}-file, if that square is free.", piece, (b'a' + file - 1) as char),
+            Mods::SpareKnight => format!("You start every game with a spare {} in your tray, which you may drop on any empty square instead of moving.", piece),
+            Mods::Sabotage(_) => format!("Your opponent starts every game one {} short.", piece),
+            Mods::Teleport => format!("Once per game, right-click your {} on your turn to swap it with any of your other pieces, as long as neither {} ends up in check.", piece, piece),
+            Mods::DoubleMove => format!("Once per game, press M or click this mod on your turn to play two moves in a row. The first of the two may not give check, so it can't mate either."),
+            Mods::Evolution(_) => format!("Every time your {} captures, it evolves into the next piece up: Pawn, Knight, Bishop, Rook, Queen.", piece),
+            Mods::Duck => format!("The next game has a duck: after every move you must put it on another empty square, where it blocks both sides. There is no check, you win by capturing the King. Used up after one game."),
+        }
+    }
+
+    /// Small 3x3 diagram illustrating the mod, as pieces placed on (column, row)
+    /// with row 0 at the top, and the squares to highlight.
+    fn diagram(&self) -> (Vec<((u8, u8), PieceType)>, Vec<(u8, u8)>) {
+        let own = self.get_piece(Colour::White).type_as_colour(Colour::White);
+        match self {
+            Mods::CrazyHouse(_) => (vec![((0, 0), PieceType::Pawn(Colour::Black)), ((1, 1), own)],
+                                   vec![(1, 0), (2, 0), (0, 1), (1, 1), (2, 1), (0, 2), (1, 2), (2, 2)]),
+            Mods::Atomic(_) => (vec![((0, 0), PieceType::Bishop(Colour::Black)), ((2, 0), PieceType::Pawn(Colour::Black)), ((1, 1), own), ((2, 2), PieceType::Rook(Colour::Black))],
+                               vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1), (0, 2), (1, 2), (2, 2)]),
+            Mods::Sniper(_) => (vec![((0, 2), own), ((2, 0), PieceType::Knight(Colour::Black))],
+                               vec![(2, 0)]),
+            Mods::Shield(_) => (vec![((1, 1), own), ((2, 2), PieceType::Queen(Colour::Black))],
+                               vec![(1, 1)]),
+            Mods::ExtraPawn(_) => (vec![((0, 2), PieceType::Pawn(Colour::White)), ((1, 2), PieceType::Pawn(Colour::White)), ((1, 1), PieceType::Pawn(Colour::White)), ((2, 2), PieceType::Pawn(Colour::White))],
+                                  vec![(1, 1)]),
+            Mods::SpareKnight => (vec![((1, 1), PieceType::Knight(Colour::White))],
+                                 vec![(0, 0), (1, 0), (2, 0), (0, 1), (2, 1), (0, 2), (1, 2), (2, 2)]),
+            Mods::Sabotage(_) => (vec![((0, 0), own.type_as_colour(Colour::Black)), ((2, 0), own.type_as_colour(Colour::Black))],
+                                 vec![(1, 0)]),
+            Mods::Teleport => (vec![((0, 2), PieceType::King(Colour::White)), ((2, 0), PieceType::Rook(Colour::White))],
+                              vec![(0, 2), (2, 0)]),
+            Mods::DoubleMove => (vec![((0, 2), PieceType::Knight(Colour::White))],
+                                vec![(1, 0), (2, 2)]),
+            Mods::KingOfTheHill => (vec![((0, 0), PieceType::King(Colour::White))],
+                                   vec![(1, 1), (2, 1), (1, 2), (2, 2)]),
+            Mods::Extinction(_) => (vec![((0, 2), PieceType::Queen(Colour::White)), ((2, 0), own.type_as_colour(Colour::Black))],
+                                   vec![(2, 0)]),
+            Mods::TripleCheck(_) => (vec![((0, 2), own), ((2, 0), PieceType::King(Colour::Black))],
+                                    vec![(2, 0)]),
+            Mods::Chess960 => (vec![((0, 2), PieceType::Bishop(Colour::White)), ((1, 2), PieceType::Rook(Colour::White)), ((2, 2), PieceType::King(Colour::White))],
+                              vec![(0, 2), (1, 2), (2, 2)]),
+            Mods::Army(army) => {
+                // the three middle files of the army's front ranks
+                let ranks = &armies()[*army].ranks;
+                let mut pieces = Vec::new();
+                for (row, rank) in ranks.iter().rev().take(3).enumerate() {
+                    for col in 0..3u8 {
+                        if let Some(piece) = rank[col as usize + 3] {
+                            pieces.push(((col, row as u8), piece.type_as_colour(Colour::White)));
+                        }
+                    }
+                }
+                (pieces, vec![(0, 2), (1, 2), (2, 2)])
+            },
+            Mods::Antichess => (vec![((0, 2), PieceType::King(Colour::White)), ((1, 1), PieceType::Pawn(Colour::Black)), ((2, 0), PieceType::Queen(Colour::Black))],
+                               vec![(1, 1)]),
+            Mods::RacingKings => (vec![((0, 1), PieceType::King(Colour::Black)), ((2, 1), PieceType::King(Colour::White))],
+                                 vec![(0, 0), (1, 0), (2, 0)]),
+            Mods::Portal(_) => (vec![((0, 2), own), ((2, 1), PieceType::Pawn(Colour::Black))],
+                               vec![(1, 2), (1, 0)]),
+            Mods::Evolution(_) => (vec![((0, 2), own), ((1, 1), PieceType::Pawn(Colour::Black)), ((2, 0), evolved(own).unwrap_or(own))],
+                                  vec![(1, 1)]),
+            Mods::Fairy(fairy) => (vec![((1, 1), fairy.base(Colour::White))],
+                                  vec![(0, 0), (1, 0), (2, 0), (0, 1), (2, 1), (0, 2), (1, 2), (2, 2)].into_iter()
+                                      .filter(|square| *square != (1, 0) && *square != (1, 2) || *fairy != Fairy::Archbishop)
+                                      .collect()),
+            // the duck itself isn't a piece type, so it's only hinted at by the blocked square
+            Mods::Duck => (vec![((0, 2), PieceType::Rook(Colour::White)), ((0, 0), PieceType::King(Colour::Black))],
+                          vec![(0, 1)]),
+        }
+    }
+
+    fn get_piece(&self, col: Colour) -> PieceType {
+        let not_col;
+        if col == Colour::White { not_col = Colour::Black } else { not_col = Colour::White }
+        match self {
+            Mods::CrazyHouse(piece) => piece.type_as_colour(not_col),
+            Mods::Atomic(piece) => piece.type_as_colour(col),
+            Mods::Sniper(piece) => piece.type_as_colour(col),
+            Mods::ExtraPawn(_) => PieceType::Pawn(col),
+            Mods::SpareKnight => PieceType::Knight(col),
+            Mods::Sabotage(piece) => piece.type_as_colour(not_col),
+            Mods::Teleport => PieceType::King(col),
+            Mods::DoubleMove => PieceType::King(col),
+            Mods::KingOfTheHill => PieceType::King(col),
+            Mods::Extinction(piece) => piece.type_as_colour(not_col),
+            Mods::TripleCheck(piece) => piece.type_as_colour(col),
+            Mods::Chess960 => PieceType::Rook(col),
+            Mods::Army(_) => PieceType::Pawn(col),
+            Mods::Antichess | Mods::RacingKings | Mods::Duck => PieceType::King(col),
+            Mods::Portal(piece) | Mods::Evolution(piece) | Mods::Shield(piece) => piece.type_as_colour(col),
+            Mods::Fairy(fairy) => fairy.base(col),
+        }
+    }
+
+    /// The same mod held by the other side, its pieces recoloured, for when the players change colours.
+    fn flipped(&self) -> Mods {
+        let flip = |piece: &PieceType| piece.type_as_colour(opposite(piece.colour()));
+        match self {
+            Mods::CrazyHouse(piece) => Mods::CrazyHouse(flip(piece)),
+            Mods::Atomic(piece) => Mods::Atomic(flip(piece)),
+            Mods::Sniper(piece) => Mods::Sniper(flip(piece)),
+            Mods::Extinction(piece) => Mods::Extinction(flip(piece)),
+            Mods::TripleCheck(piece) => Mods::TripleCheck(flip(piece)),
+            Mods::Portal(piece) => Mods::Portal(flip(piece)),
+            Mods::Evolution(piece) => Mods::Evolution(flip(piece)),
+            Mods::Shield(piece) => Mods::Shield(flip(piece)),
+            Mods::Sabotage(piece) => Mods::Sabotage(flip(piece)),
+            _ => *self,
+        }
+    }
+}
+
+/// Rule set for a whole game, chosen for the series or by a one-game mod.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Variant {
+    Standard,
+    Antichess,
+    RacingKings,
+    Duck,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ScreenState {
+    GameScreen,
+    ScoreScreen,
+    ModScreen,
+    HelpScreen,
+    EditorScreen,
+    StatsScreen,
+    ProfileScreen,
+}
+
+/// How a game came to an end, as kept in the match history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Termination {
+    Checkmate,
+    Stalemate,
+    Hill,
+    Race,
+    Extinction,
+    TripleCheck,
+    Explosion,
+    KingCaptured,
+    /// The side to move ran out of moves in a variant where that decides the game.
+    NoMoves,
+    Repetition,
+    Wipeout,
+    Time,
+    /// The result came from the partner board in bughouse.
+    PartnerBoard,
+}
+
+impl Termination {
+    fn name(&self) -> &'static str {
+        match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::Hill => "king of the hill",
+            Termination::Race => "race",
+            Termination::Extinction => "extinction",
+            Termination::TripleCheck => "triple check",
+            Termination::Explosion => "explosion",
+            Termination::KingCaptured => "king captured",
+            Termination::NoMoves => "no moves",
+            Termination::Repetition => "repetition",
+            Termination::Wipeout => "wipeout",
+            Termination::Time => "time",
+            Termination::PartnerBoard => "partner board",
+        }
+    }
+}
+
+/// Tint a player draws their pieces with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PieceSet {
+    Classic,
+    Jade,
+    Ruby,
+    Sapphire,
+}
+
+impl PieceSet {
+    fn name(&self) -> &'static str {
+        match self {
+            PieceSet::Classic => "Classic",
+            PieceSet::Jade => "Jade",
+            PieceSet::Ruby => "Ruby",
+            PieceSet::Sapphire => "Sapphire",
+        }
+    }
+
+    /// The set after this one, for cycling through them on the profile screen.
+    fn next(&self) -> PieceSet {
+        match self {
+            PieceSet::Classic => PieceSet::Jade,
+            PieceSet::Jade => PieceSet::Ruby,
+            PieceSet::Ruby => PieceSet::Sapphire,
+            PieceSet::Sapphire => PieceSet::Classic,
+        }
+    }
+
+    fn tint(&self) -> Color {
+        match self {
+            PieceSet::Classic => Color::new(1.0, 1.0, 1.0, 1.0),
+            PieceSet::Jade => Color::new(0.6, 1.0, 0.7, 1.0),
+            PieceSet::Ruby => Color::new(1.0, 0.6, 0.6, 1.0),
+            PieceSet::Sapphire => Color::new(0.6, 0.75, 1.0, 1.0),
+        }
+    }
+}
+
+/// A local player, stored as a tab-separated line of `profiles.txt`.
+#[derive(Debug, Clone, PartialEq)]
+struct Profile {
+    name: String,
+    set: PieceSet,
+    /// The side the player is seated on when it is free.
+    side: Colour,
+    rating: f32,
+    games: u32,
+}
+
+impl Profile {
+    fn new(name: &str) -> Profile {
+        Profile {
+            name: name.to_string(),
+            set: PieceSet::Classic,
+            side: Colour::White,
+            rating: START_RATING,
+            games: 0,
+        }
+    }
+
+    fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{:.1}\t{}", self.name, self.set.name(), if self.side == Colour::White { "w" } else { "b" }, self.rating, self.games)
+    }
+
+    /// Reads a line written by `to_line`, or None if it isn't one.
+    fn parse(line: &str) -> Option<Profile> {
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() != 5 || fields[0].is_empty() {
+            return None;
+        }
+        Some(Profile {
+            name: fields[0].to_string(),
+            set: [PieceSet::Classic, PieceSet::Jade, PieceSet::Ruby, PieceSet::Sapphire].iter().find(|set| set.name() == fields[1]).copied()?,
+            side: match fields[2] {
+                "w" => Colour::White,
+                "b" => Colour::Black,
+                _ => return None,
+            },
+            rating: fields[3].parse().ok()?,
+            games: fields[4].parse().ok()?,
+        })
+    }
+}
+
+/// One finished game of the match history, stored as a tab-separated line of `history.txt`.
+#[derive(Debug, Clone, PartialEq)]
+struct GameRecord {
+    date: String,
+    /// When the series the game was part of started, in seconds since the Unix epoch.
+    series: u64,
+    /// Names of the White and Black players.
+    players: [String; 2],
+    /// Display names of the mods White and Black held.
+    mods: [Vec<String>; 2],
+    winner: Option<Colour>,
+    termination: String,
+    moves: usize,
+}
+
+impl GameRecord {
+    fn to_line(&self) -> String {
+        let clean = |text: &str| text.replace('\t', " ").replace('\n', " ");
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.date, self.series, clean(&self.players[0]), clean(&self.players[1]),
+            self.mods[0].iter().map(|name| clean(name)).collect::<Vec<String>>().join(";"),
+            self.mods[1].iter().map(|name| clean(name)).collect::<Vec<String>>().join(";"),
+            match self.winner {
+                Some(Colour::White) => "w",
+                Some(Colour::Black) => "b",
+                None => "d",
+            },
+            self.termination, self.moves)
+    }
+
+    /// Reads a line written by `to_line`, or None if it isn't one.
+    fn parse(line: &str) -> Option<GameRecord> {
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() != 9 {
+            return None;
+        }
+        let mods = |field: &str| field.split(';').filter(|name| !name.is_empty()).map(|name| name.to_string()).collect::<Vec<String>>();
+        Some(GameRecord {
+            date: fields[0].to_string(),
+            series: fields[1].parse().ok()?,
+            players: [fields[2].to_string(), fields[3].to_string()],
+            mods: [mods(fields[4]), mods(fields[5])],
+            winner: match fields[6] {
+                "w" => Some(Colour::White),
+                "b" => Some(Colour::Black),
+                "d" => None,
+                _ => return None,
+            },
+            termination: fields[7].to_string(),
+            moves: fields[8].parse().ok()?,
+        })
+    }
+}
+
+/// Replacement starting army for the Army mod, loaded from `resources/armies.txt`.
+struct ArmyTemplate {
+    name: String,
+    description: String,
+    /// Whether the holder also loses once only their king is left.
+    wipeout: bool,
+    /// The holder's ranks from their back rank forwards, by file.
+    ranks: Vec<Vec<Option<PieceType>>>,
+}
+
+/// A scripted position demonstrating a single mod held by White.
+struct Lesson {
+    modi: Mods,
+    position: &'static str,
+    tray: Option<PieceType>,
+    triple_checks: u8,
+    portals: Option<((u8, u8), (u8, u8))>,
+    duck: Option<(u8, u8)>,
+    prompt: &'static str,
+    from: (isize, isize),
+    to: (isize, isize),
+    outcome: &'static str,
+}
+
+/// Defaults for the lesson fields most lessons don't need.
+const LESSON: Lesson = Lesson {
+    modi: Mods::KingOfTheHill,
+    position: "4k3/8/8/8/8/8/8/4K3",
+    tray: None,
+    triple_checks: 0,
+    portals: None,
+    duck: None,
+    prompt: "",
+    from: (0, 0),
+    to: (0, 0),
+    outcome: "",
+};
+
+/// Everything that makes up a game in progress, so it can be put aside and brought back later.
+#[derive(Clone)]
+struct GameSnapshot {
+    board: HashMap<Position, PieceType>,
+    active_color: Colour,
+    promotion: [PieceType; 2],
+    taken_black_pieces: Vec<PieceType>,
+    taken_white_pieces: Vec<PieceType>,
+    white_mods: HashSet<Mods>,
+    black_mods: HashSet<Mods>,
+    triple_check_counter: (u8, u8),
+    castling: [bool; 4],
+    castling_files: [(u8, u8, u8); 2],
+    start_fen: String,
+    move_log: Vec<String>,
+    variant: Variant,
+    racing_finish: bool,
+    duck: Option<(u8, u8)>,
+    duck_pending: bool,
+    promoted: HashSet<(u8, u8)>,
+    position_history: Vec<String>,
+    portals: Option<((u8, u8), (u8, u8))>,
+    fairies: HashMap<(u8, u8), Fairy>,
+    shields: HashSet<(u8, u8)>,
+    double_used: (bool, bool),
+    double_armed: bool,
+    spare_knights: (bool, bool),
+    teleport_used: (bool, bool),
+    evolving: HashSet<(u8, u8)>,
+}
+
+impl GameSnapshot {
+    /// The game as lines of `key value`, with a line per entry for lists.
+    fn to_text(&self) -> String {
+        let flag = |on: bool| if on { "1" } else { "0" };
+        let square = |square: &(u8, u8)| Position { file: square.0, rank: square.1 }.to_string();
+        let mut lines = Vec::new();
+        lines.push(format!("board {}", fen_placement(&self.board, &HashMap::new())));
+        lines.push(format!("turn {}", if self.active_color == Colour::White { "w" } else { "b" }));
+        lines.push(format!("promotion {} {}", piece_char(self.promotion[0]), piece_char(self.promotion[1])));
+        let pockets = self.taken_black_pieces.iter().map(|piece| piece_char(piece.type_as_colour(Colour::White)))
+            .chain(self.taken_white_pieces.iter().map(|piece| piece_char(piece.type_as_colour(Colour::Black))))
+            .collect::<String>();
+        lines.push(format!("trays {}", pockets));
+        for modi in self.white_mods.iter() {
+            lines.push(format!("mod w {}", modi.display_name(Colour::White)));
+        }
+        for modi in self.black_mods.iter() {
+            lines.push(format!("mod b {}", modi.display_name(Colour::Black)));
+        }
+        lines.push(format!("triple_checks {} {}", self.triple_check_counter.0, self.triple_check_counter.1));
+        lines.push(format!("castling {}", self.castling.iter().map(|allowed| flag(*allowed)).collect::<Vec<&str>>().join(" ")));
+        let files = self.castling_files;
+        lines.push(format!("castling_files {} {} {} {} {} {}", files[0].0, files[0].1, files[0].2, files[1].0, files[1].1, files[1].2));
+        lines.push(format!("start {}", self.start_fen));
+        lines.push(format!("variant {:?}", self.variant));
+        lines.push(format!("racing_finish {}", flag(self.racing_finish)));
+        if let Some(duck) = &self.duck {
+            lines.push(format!("duck {}", square(duck)));
+        }
+        lines.push(format!("duck_pending {}", flag(self.duck_pending)));
+        if let Some((a, b)) = &self.portals {
+            lines.push(format!("portals {} {}", square(a), square(b)));
+        }
+        for (at, fairy) in self.fairies.iter() {
+            lines.push(format!("fairy {} {}", square(at), fairy.name()));
+        }
+        for (key, squares) in [("promoted", &self.promoted), ("shield", &self.shields), ("evolving", &self.evolving)].iter() {
+            for at in squares.iter() {
+                lines.push(format!("{} {}", key, square(at)));
+            }
+        }
+        lines.push(format!("double_used {} {}", flag(self.double_used.0), flag(self.double_used.1)));
+        lines.push(format!("double_armed {}", flag(self.double_armed)));
+        lines.push(format!("spare_knights {} {}", flag(self.spare_knights.0), flag(self.spare_knights.1)));
+        lines.push(format!("teleport_used {} {}", flag(self.teleport_used.0), flag(self.teleport_used.1)));
+        for notation in self.move_log.iter() {
+            lines.push(format!("move {}", notation));
+        }
+        for fen in self.position_history.iter() {
+            lines.push(format!("history {}", fen));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Reads back a game written by `to_text`, None if any line doesn't make sense.
+    fn parse(text: &str) -> Option<GameSnapshot> {
+        let mut snapshot = GameSnapshot {
+            board: HashMap::new(),
+            active_color: Colour::White,
+            promotion: [PieceType::Queen(Colour::White), PieceType::Queen(Colour::Black)],
+            taken_black_pieces: Vec::new(),
+            taken_white_pieces: Vec::new(),
+            white_mods: HashSet::new(),
+            black_mods: HashSet::new(),
+            triple_check_counter: (0, 0),
+            castling: [false; 4],
+            castling_files: [(5, 8, 1); 2],
+            start_fen: STANDARD_FEN.to_string(),
+            move_log: Vec::new(),
+            variant: Variant::Standard,
+            racing_finish: false,
+            duck: None,
+            duck_pending: false,
+            promoted: HashSet::new(),
+            position_history: Vec::new(),
+            portals: None,
+            fairies: HashMap::new(),
+            shields: HashSet::new(),
+            double_used: (false, false),
+            double_armed: false,
+            spare_knights: (false, false),
+            teleport_used: (false, false),
+            evolving: HashSet::new(),
+        };
+        let flag = |word: &str| match word {
+            "1" => Some(true),
+            "0" => Some(false),
+            _ => None,
+        };
+        let square = |word: &str| Position::from_string(word.to_string()).ok().map(|pos| (pos.file, pos.rank));
+        for line in text.lines() {
+            let (key, value) = match line.find(' ') {
+                Some(space) => (&line[..space], &line[space + 1..]),
+                None => (line, ""),
+            };
+            let words = value.split_whitespace().collect::<Vec<&str>>();
+            match (key, words.as_slice()) {
+                ("board", [placement]) => snapshot.board = load_position(placement, Colour::White).board,
+                ("turn", ["w"]) => snapshot.active_color = Colour::White,
+                ("turn", ["b"]) => snapshot.active_color = Colour::Black,
+                ("promotion", [white, black]) => {
+                    snapshot.promotion = [piece_from_char(white.chars().next()?)?, piece_from_char(black.chars().next()?)?];
+                },
+                ("trays", pockets) => {
+                    let pockets = pockets.concat();
+                    snapshot.taken_black_pieces = pockets.chars().filter(|c| c.is_ascii_uppercase()).filter_map(piece_from_char).map(|piece| piece.type_as_colour(Colour::Black)).collect();
+                    snapshot.taken_white_pieces = pockets.chars().filter(|c| c.is_ascii_lowercase()).filter_map(piece_from_char).map(|piece| piece.type_as_colour(Colour::White)).collect();
+                },
+                ("mod", [side, ..]) => {
+                    let (col, mods) = if *side == "w" { (Colour::White, &mut snapshot.white_mods) } else { (Colour::Black, &mut snapshot.black_mods) };
+                    let name = value[side.len()..].trim();
+                    mods.insert(all_mods(col).into_iter().find(|modi| modi.display_name(col) == name)?);
+                },
+                ("triple_checks", [white, black]) => snapshot.triple_check_counter = (white.parse().ok()?, black.parse().ok()?),
+                ("castling", [a, b, c, d]) => snapshot.castling = [flag(a)?, flag(b)?, flag(c)?, flag(d)?],
+                ("castling_files", files) if files.len() == 6 => {
+                    let files = files.iter().map(|file| file.parse().ok()).collect::<Option<Vec<u8>>>()?;
+                    snapshot.castling_files = [(files[0], files[1], files[2]), (files[3], files[4], files[5])];
+                },
+                ("start", _) => snapshot.start_fen = value.to_string(),
+                ("variant", [name]) => {
+                    snapshot.variant = *[Variant::Standard, Variant::Antichess, Variant::RacingKings, Variant::Duck].iter().find(|variant| format!("{:?}", variant) == *name)?;
+                },
+                ("racing_finish", [on]) => snapshot.racing_finish = flag(on)?,
+                ("duck", [at]) => snapshot.duck = Some(square(at)?),
+                ("duck_pending", [on]) => snapshot.duck_pending = flag(on)?,
+                ("portals", [a, b]) => snapshot.portals = Some((square(a)?, square(b)?)),
+                ("fairy", [at, name]) => {
+                    let fairy = *[Fairy::Archbishop, Fairy::Chancellor, Fairy::Amazon].iter().find(|fairy| fairy.name() == *name)?;
+                    snapshot.fairies.insert(square(at)?, fairy);
+                },
+                ("promoted", [at]) => { snapshot.promoted.insert(square(at)?); },
+                ("shield", [at]) => { snapshot.shields.insert(square(at)?); },
+                ("evolving", [at]) => { snapshot.evolving.insert(square(at)?); },
+                ("double_used", [white, black]) => snapshot.double_used = (flag(white)?, flag(black)?),
+                ("double_armed", [on]) => snapshot.double_armed = flag(on)?,
+                ("spare_knights", [white, black]) => snapshot.spare_knights = (flag(white)?, flag(black)?),
+                ("teleport_used", [white, black]) => snapshot.teleport_used = (flag(white)?, flag(black)?),
+                ("move", _) => snapshot.move_log.push(value.to_string()),
+                ("history", _) => snapshot.position_history.push(value.to_string()),
+                ("", []) => (),
+                _ => return None,
+            }
+        }
+        Some(snapshot)
+    }
+}
+
+/// Progress through the tutorial, along with the game it interrupted.
+struct Tutorial {
+    lesson: usize,
+    done: bool,
+    saved: GameSnapshot,
+    screen: ScreenState,
+}
+
+/// Line based connection to the machine playing the other bughouse board.
+pub struct Link {
+    /// Listens for the partner machine while hosting, until it has connected.
+    listener: Option<TcpListener>,
+    stream: Option<TcpStream>,
+    incoming: String,
+    /// What the link is waiting for or what went wrong, shown next to the bughouse clocks.
+    status: String,
+}
+
+impl Link {
+    /// Listens on `port` for the partner machine, which `accept` then picks up without blocking.
+    pub fn host(port: &str) -> std::io::Result<Link> {
+        let listener = TcpListener::bind(format!("0.0.0.0:{}", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Link { listener: Some(listener), stream: None, incoming: String::new(), status: format!("Waiting for the partner board on port {}...", port) })
+    }
+
+    /// Connects to a machine hosting the other board.
+    pub fn join(address: &str) -> std::io::Result<Link> {
+        Link::new(TcpStream::connect(address)?)
+    }
+
+    fn new(stream: TcpStream) -> std::io::Result<Link> {
+        stream.set_nonblocking(true)?;
+        Ok(Link { listener: None, stream: Some(stream), incoming: String::new(), status: String::new() })
+    }
+
+    /// Whether the partner machine is connected.
+    fn connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Takes the partner machine's connection if it has come in while hosting.
+    fn accept(&mut self) {
+        let accepted = match self.listener.as_ref() {
+            Some(listener) => listener.accept(),
+            None => return,
+        };
+        match accepted.and_then(|(stream, _)| stream.set_nonblocking(true).map(|()| stream)) {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.listener = None;
+                self.status = String::new();
+            },
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => (),
+            Err(e) => self.status = format!("Couldn't connect the partner board: {}", e),
+        }
+    }
+
+    fn send(&mut self, line: &str) {
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => return,
+        };
+        if let Err(e) = stream.write_all(format!("{}\n", line).as_bytes()) {
+            self.status = format!("Lost the partner board: {}", e);
+        }
+    }
+
+    /// Complete lines received since the last call.
+    fn receive(&mut self) -> Vec<String> {
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => return Vec::new(),
+        };
+        let mut buffer = [0u8; 1024];
+        loop {
+            match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => self.incoming.push_str(&String::from_utf8_lossy(&buffer[..n])),
+                Err(_) => break,
+            }
+        }
+        let mut lines = Vec::new();
+        while let Some(end) = self.incoming.find('\n') {
+            lines.push(self.incoming[..end].to_string());
+            self.incoming = self.incoming[end + 1..].to_string();
+        }
+        lines
+    }
+}
+
+/// The second board of a bughouse game. White on board A plays with Black on board B,
+/// and whichever board is being played sits in `AppState.board` while the other is kept here.
+struct Bughouse {
+    board: Game,
+    taken_black_pieces: Vec<PieceType>,
+    taken_white_pieces: Vec<PieceType>,
+    move_log: Vec<String>,
+    castling: [bool; 4],
+    /// Promoted pieces on this board, kept while the other board is played.
+    promoted: HashSet<(u8, u8)>,
+    /// Positions this board has been through, for spotting repetitions once it is played again.
+    position_history: Vec<String>,
+    /// Remaining (white, black) seconds on the played board and on this one.
+    clocks: [(f32, f32); 2],
+    /// Whether board B is the one being played.
+    swapped: bool,
+    /// How many tray pieces of the played board came from this board and may be dropped.
+    received: (usize, usize),
+    /// Moves of the played board already sent to the partner machine.
+    sent_moves: usize,
+    link: Option<Link>,
+}
+
+impl Bughouse {
+    fn new(link: Option<Link>, swapped: bool) -> Bughouse {
+        Bughouse {
+            board: Game::new(),
+            taken_black_pieces: Vec::new(),
+            taken_white_pieces: Vec::new(),
+            move_log: Vec::new(),
+            castling: [true; 4],
+            promoted: HashSet::new(),
+            position_history: Vec::new(),
+            clocks: [(BUGHOUSE_CLOCK, BUGHOUSE_CLOCK); 2],
+            swapped: swapped,
+            received: (0, 0),
+            sent_moves: 0,
+            link: link,
+        }
+    }
+}
+
+/// GUI logic and event implementation structure. 
+pub struct AppState {
+    sprites: HashMap<PieceType, graphics::Image>,
+    /// White and black sprite of each fairy piece.
+    fairy_sprites: HashMap<Fairy, [graphics::Image; 2]>,
+    duck_sprite: Option<graphics::Image>,
+    board: Game,
+    selected_pos: (isize, isize),
+    highlighted_pos: Vec<(isize, isize)>,
+    taken_black_pieces: Vec<PieceType>,
+    taken_white_pieces: Vec<PieceType>,
+    white_mods: HashSet<Mods>,
+    black_mods: HashSet<Mods>,
+    triple_check_counter: (u8, u8),
+    wins: (u8, u8),
+    screen: ScreenState,
+    cur_winner: Option<Colour>,
+    /// The three offers on the mod screen, None where no mod was left to offer.
+    random_mods: Vec<Option<Mods>>,
+    rerolls: (u8, u8),
+    white_banished: HashSet<Mods>,
+    black_banished: HashSet<Mods>,
+    win_spent: bool,
+    swapped: (bool, bool),
+    mouse_pos: (f32, f32),
+    help_return: ScreenState,
+    tutorial: Option<Tutorial>,
+    castling: [bool; 4],
+    castling_files: [(u8, u8, u8); 2],
+    start_fen: String,
+    move_log: Vec<String>,
+    help_page: usize,
+    variant: Variant,
+    series_variant: Variant,
+    racing_finish: bool,
+    duck: Option<(u8, u8)>,
+    duck_pending: bool,
+    /// Squares holding pieces that were promoted from pawns, which go back to the tray as pawns.
+    promoted: HashSet<(u8, u8)>,
+    /// FEN after every move of the game, pockets included, for spotting repetitions.
+    position_history: Vec<String>,
+    bughouse: Option<Bughouse>,
+    /// The two linked portal squares, when someone holds a Portal mod.
+    portals: Option<((u8, u8), (u8, u8))>,
+    /// Squares holding fairy pieces, on top of the base piece the library sees there.
+    fairies: HashMap<(u8, u8), Fairy>,
+    /// Squares holding pieces whose shield is still up.
+    shields: HashSet<(u8, u8)>,
+    /// Square whose shield the move being played broke, for the move log.
+    shield_hit: Option<(u8, u8)>,
+    /// Whether White and Black have spent their double move this game.
+    double_used: (bool, bool),
+    /// Whether the side to move has called its double move, keeping the turn after the next move.
+    double_armed: bool,
+    /// Whether White and Black still have the knight from a Spare Knight mod in their tray.
+    spare_knights: (bool, bool),
+    /// Whether White and Black have used their king teleport this game.
+    teleport_used: (bool, bool),
+    /// Whether the king is selected for a teleport rather than a move.
+    teleporting: bool,
+    /// Centres of recent atomic explosions and how long they have left on screen.
+    explosions: Vec<((u8, u8), f32)>,
+    /// Squares blown up by the move being played, for the move log.
+    blast: Vec<(u8, u8)>,
+    /// What the piece that just captured evolved into, for the move log.
+    evolution: Option<PieceType>,
+    /// Squares holding pieces that have evolved, which go on evolving whatever they have become.
+    evolving: HashSet<(u8, u8)>,
+    /// The game before each move played so far, for taking moves back.
+    undo_stack: Vec<GameSnapshot>,
+    editor_piece: Option<PieceType>,
+    editor_mod: usize,
+    editor_message: String,
+    /// The game as it was when the editor was opened, for Escape to bring back.
+    editor_backup: Option<GameSnapshot>,
+    /// The standard setup of the game about to start, kept while a series variant key has replaced it.
+    variant_backup: Option<GameSnapshot>,
+    /// Whether finished games are written to the history file, which headless games aren't.
+    keep_history: bool,
+    history: Vec<GameRecord>,
+    /// When the current series started, in seconds since the Unix epoch.
+    series_start: u64,
+    /// Names of the White and Black players.
+    players: [String; 2],
+    /// Mods White and Black held when the current game started, one-game variants included.
+    game_mods: [Vec<String>; 2],
+    stats_return: ScreenState,
+    profiles: Vec<Profile>,
+    /// Profiles playing White and Black, if they aren't guests.
+    seats: [Option<usize>; 2],
+    /// Name being typed in for a new profile.
+    new_profile: String,
+    /// Whether the mods each side held count towards the expected result when rating a game.
+    rate_mods: bool,
+    /// Rating points White gained in the game just finished, negative for a loss.
+    rating_change: Option<f32>,
+    /// Whether the players change colours after every game of the series.
+    alternate: bool,
+}
+
+impl AppState {
+    /// Initialise new application, i.e. initialise new game and load resources.
+    pub fn new(ctx: &mut Context) -> GameResult<AppState> {
+        let sprites = AppState::load_sprites();
+        let mut state = AppState::headless();
+        state.sprites = sprites
+            .iter()
+            .map(|_sprite| {
+                (_sprite.0, graphics::Image::new(ctx, _sprite.1.clone()).unwrap())
+            })
+            .collect::<HashMap<PieceType, graphics::Image>>();
+        state.fairy_sprites = [Fairy::Archbishop, Fairy::Chancellor, Fairy::Amazon].iter()
+            .map(|fairy| {
+                let name = fairy.name().to_lowercase();
+                (*fairy, [graphics::Image::new(ctx, format!("/white_{}.png", name)).unwrap(),
+                          graphics::Image::new(ctx, format!("/black_{}.png", name)).unwrap()])
+            })
+            .collect::<HashMap<Fairy, [graphics::Image; 2]>>();
+        state.duck_sprite = Some(graphics::Image::new(ctx, "/duck.png").unwrap());
+        state.keep_history = true;
+        state.history = load_history();
+        state.profiles = load_profiles();
+        state.screen = ScreenState::ProfileScreen;
+
+        Ok(state)
+    }
+
+    /// Starts networked bughouse over `link`, on board A for the host and on board B for the machine that joined.
+    pub fn connect(&mut self, link: Link, joined: bool) {
+        self.bughouse = Some(Bughouse::new(Some(link), joined));
+        self.screen = ScreenState::GameScreen;
+    }
+
+    /// A new game with no sprites loaded, for playing without a window.
+    fn headless() -> AppState {
+        let mut board = Game::new();
+        board.set_promotion("queen".to_string());
+
+        AppState {
+            sprites: HashMap::new(),
+            fairy_sprites: HashMap::new(),
+            duck_sprite: None,
+            board: board,
+            selected_pos: (0, 0),
+            highlighted_pos: Vec::new(),
+            taken_black_pieces: Vec::new(),
+            taken_white_pieces: Vec::new(),
+            white_mods: HashSet::new(),
+            black_mods: HashSet::new(),
+            triple_check_counter: (0, 0),
+            wins: (0, 0),
+            screen: ScreenState::GameScreen,
+            cur_winner: None,
+            random_mods: Vec::new(),
+            rerolls: (STARTING_REROLLS, STARTING_REROLLS),
+            white_banished: HashSet::new(),
+            black_banished: HashSet::new(),
+            win_spent: false,
+            swapped: (false, false),
+            mouse_pos: (0.0, 0.0),
+            help_return: ScreenState::GameScreen,
+            tutorial: None,
+            castling: [true; 4],
+            castling_files: [(5, 8, 1); 2],
+            start_fen: STANDARD_FEN.to_string(),
+            move_log: Vec::new(),
+            help_page: 0,
+            variant: Variant::Standard,
+            series_variant: Variant::Standard,
+            racing_finish: false,
+            duck: None,
+            duck_pending: false,
+            promoted: HashSet::new(),
+            position_history: Vec::new(),
+            bughouse: None,
+            portals: None,
+            fairies: HashMap::new(),
+            shields: HashSet::new(),
+            shield_hit: None,
+            double_used: (false, false),
+            double_armed: false,
+            spare_knights: (false, false),
+            teleport_used: (false, false),
+            teleporting: false,
+            explosions: Vec::new(),
+            blast: Vec::new(),
+            evolution: None,
+            evolving: HashSet::new(),
+            undo_stack: Vec::new(),
+            editor_piece: Some(PieceType::Pawn(Colour::White)),
+            editor_mod: 0,
+            editor_message: String::new(),
+            editor_backup: None,
+            variant_backup: None,
+            keep_history: false,
+            history: Vec::new(),
+            series_start: unix_time(),
+            players: ["White".to_string(), "Black".to_string()],
+            game_mods: [Vec::new(), Vec::new()],
+            stats_return: ScreenState::GameScreen,
+            profiles: Vec::new(),
+            seats: [None, None],
+            new_profile: String::new(),
+            rate_mods: true,
+            rating_change: None,
+            alternate: false,
+        }
+    }
+
+    /// Loads chess piese images into vector.
+    fn load_sprites() -> Vec<(PieceType, String)> {
+        let mut sprites = Vec::new();
+        sprites.push(((PieceType::King(Colour::Black)), "/black_king.png".to_string()));
+        sprites.push(((PieceType::Queen(Colour::Black)), "/black_queen.png".to_string()));
+        sprites.push(((PieceType::Rook(Colour::Black)), "/black_rook.png".to_string()));
+        sprites.push(((PieceType::Pawn(Colour::Black)), "/black_pawn.png".to_string()));
+        sprites.push(((PieceType::Bishop(Colour::Black)), "/black_bishop.png".to_string()));
+        sprites.push(((PieceType::Knight(Colour::Black)), "/black_knight.png".to_string()));
+        sprites.push(((PieceType::King(Colour::White)), "/white_king.png".to_string()));
+        sprites.push(((PieceType::Queen(Colour::White)), "/white_queen.png".to_string()));
+        sprites.push(((PieceType::Rook(Colour::White)), "/white_rook.png".to_string()));
+        sprites.push(((PieceType::Pawn(Colour::White)), "/white_pawn.png".to_string()));
+        sprites.push(((PieceType::Bishop(Colour::White)), "/white_bishop.png".to_string()));
+        sprites.push(((PieceType::Knight(Colour::White)), "/white_knight.png".to_string()));
+        sprites
+    }
+
+    fn end_game(&mut self, winner: Option<Colour>, reason: Termination) {
+        if let Some(tutorial) = self.tutorial.as_mut() {
+            tutorial.done = true;
+            return;
+        }
+        // bughouse results are kept from the point of view of board A
+        let winner = match &self.bughouse {
+            Some(bughouse) if bughouse.swapped => winner.map(opposite),
+            _ => winner,
+        };
+        if self.screen == ScreenState::GameScreen {
+            if let Some(link) = self.bughouse.as_mut().and_then(|bughouse| bughouse.link.as_mut()) {
+                link.send(&format!("result {}", match winner {
+                    Some(Colour::White) => "w",
+                    Some(Colour::Black) => "b",
+                    None => "d",
+                }));
+            }
+        }
+        self.record_result(winner, reason);
+    }
+
+    /// Counts a finished game towards the series, adds it to the match history and shows the score.
+    fn record_result(&mut self, winner: Option<Colour>, reason: Termination) {
+        if self.screen == ScreenState::GameScreen {
+            match winner {
+                Some(Colour::White) => self.wins.0 += 1,
+                Some(Colour::Black) => self.wins.1 += 1,
+                None => (),
+            }
+            if self.keep_history {
+                let record = GameRecord {
+                    date: civil_date(unix_time() / 86400),
+                    series: self.series_start,
+                    players: self.players.clone(),
+                    mods: self.game_mods.clone(),
+                    winner: winner,
+                    termination: reason.name().to_string(),
+                    moves: self.move_log.len(),
+                };
+                if let Err(e) = append_history(&record) {
+                    println!("Couldn't save {}: {}", HISTORY_FILE, e);
+                }
+                self.history.push(record);
+            }
+            self.rating_change = None;
+            if let ([Some(white), Some(black)], None) = (self.seats, &self.bughouse) {
+                self.rate_game(white, black, winner);
+            }
+            self.win_spent = false;
+            self.swapped = (false, false);
+        }
+        self.screen = ScreenState::ScoreScreen;
+        self.cur_winner = winner;
+    }
+
+    /// The game in progress, everything but the series around it.
+    fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            board: self.board.board.clone(),
+            active_color: self.board.active_color,
+            promotion: self.board.promotion,
+            taken_black_pieces: self.taken_black_pieces.clone(),
+            taken_white_pieces: self.taken_white_pieces.clone(),
+            white_mods: self.white_mods.clone(),
+            black_mods: self.black_mods.clone(),
+            triple_check_counter: self.triple_check_counter,
+            castling: self.castling,
+            castling_files: self.castling_files,
+            start_fen: self.start_fen.clone(),
+            move_log: self.move_log.clone(),
+            variant: self.variant,
+            racing_finish: self.racing_finish,
+            duck: self.duck,
+            duck_pending: self.duck_pending,
+            promoted: self.promoted.clone(),
+            position_history: self.position_history.clone(),
+            portals: self.portals,
+            fairies: self.fairies.clone(),
+            shields: self.shields.clone(),
+            double_used: self.double_used,
+            double_armed: self.double_armed,
+            spare_knights: self.spare_knights,
+            teleport_used: self.teleport_used,
+            evolving: self.evolving.clone(),
+        }
+    }
+
+    /// Puts back a game taken with `snapshot`, with nothing selected.
+    fn restore(&mut self, snapshot: GameSnapshot) {
+        self.board = Game::new();
+        self.board.board = snapshot.board;
+        self.board.active_color = snapshot.active_color;
+        self.board.promotion = snapshot.promotion;
+        self.taken_black_pieces = snapshot.taken_black_pieces;
+        self.taken_white_pieces = snapshot.taken_white_pieces;
+        self.white_mods = snapshot.white_mods;
+        self.black_mods = snapshot.black_mods;
+        self.triple_check_counter = snapshot.triple_check_counter;
+        self.castling = snapshot.castling;
+        self.castling_files = snapshot.castling_files;
+        self.start_fen = snapshot.start_fen;
+        self.move_log = snapshot.move_log;
+        self.variant = snapshot.variant;
+        self.racing_finish = snapshot.racing_finish;
+        self.duck = snapshot.duck;
+        self.duck_pending = snapshot.duck_pending;
+        self.promoted = snapshot.promoted;
+        self.position_history = snapshot.position_history;
+        self.portals = snapshot.portals;
+        self.fairies = snapshot.fairies;
+        self.shields = snapshot.shields;
+        self.double_used = snapshot.double_used;
+        self.double_armed = snapshot.double_armed;
+        self.spare_knights = snapshot.spare_knights;
+        self.teleport_used = snapshot.teleport_used;
+        self.evolving = snapshot.evolving;
+        self.teleporting = false;
+        self.selected_pos = (0, 0);
+        self.highlighted_pos = Vec::new();
+    }
+
+    /// Takes back the last move, along with the duck placed after it.
+    fn undo(&mut self) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            self.restore(snapshot);
+        }
+    }
+
+    /// Writes the game in progress to `SAVE_FILE`. The series around it, score and offers, isn't saved.
+    fn save_game(&self) {
+        if let Err(e) = std::fs::write(SAVE_FILE, self.snapshot().to_text()) {
+            println!("Couldn't save {}: {}", SAVE_FILE, e);
+        }
+    }
+
+    /// Replaces the game in progress with the one in `SAVE_FILE`.
+    fn load_game(&mut self) {
+        let text = match std::fs::read_to_string(SAVE_FILE) {
+            Ok(text) => text,
+            Err(e) => {
+                println!("Couldn't load {}: {}", SAVE_FILE, e);
+                return;
+            },
+        };
+        match GameSnapshot::parse(&text) {
+            Some(snapshot) => {
+                self.restore(snapshot);
+                self.game_mods = self.held_mod_names();
+                self.undo_stack = Vec::new();
+            },
+            None => println!("Couldn't load {}: not a saved game", SAVE_FILE),
+        }
+    }
+
+    /// Sets up the given tutorial lesson, putting the current game aside if needed.
+    fn start_tutorial(&mut self, lesson: usize) {
+        match self.tutorial.as_mut() {
+            Some(tutorial) => {
+                tutorial.lesson = lesson;
+                tutorial.done = false;
+            },
+            None => {
+                self.tutorial = Some(Tutorial {
+                    lesson: lesson,
+                    done: false,
+                    saved: self.snapshot(),
+                    screen: if self.screen == ScreenState::HelpScreen { self.help_return } else { self.screen },
+                });
+            },
+        }
+
+        let lesson = &lessons()[lesson];
+        self.board = load_position(lesson.position, Colour::White);
+        self.taken_black_pieces = lesson.tray.into_iter().collect();
+        self.taken_white_pieces = Vec::new();
+        self.white_mods = [lesson.modi].iter().cloned().collect();
+        self.black_mods = HashSet::new();
+        self.triple_check_counter = (lesson.triple_checks, 0);
+        self.variant = match lesson.modi {
+            Mods::Antichess => Variant::Antichess,
+            Mods::RacingKings => Variant::RacingKings,
+            Mods::Duck => Variant::Duck,
+            _ => Variant::Standard,
+        };
+        self.racing_finish = false;
+        self.duck = lesson.duck;
+        self.duck_pending = false;
+        self.castling = [false; 4];
+        self.castling_files = [(5, 8, 1); 2];
+        if lesson.modi == Mods::Chess960 {
+            // White may castle with the two rooks on its back rank, wherever they stand
+            let files = |piece: PieceType| (1..9).filter(|file| self.board.board.get(&Position { file: *file, rank: 1 }) == Some(&piece)).collect::<Vec<u8>>();
+            if let (Some(king), [queenside, kingside]) = (files(PieceType::King(Colour::White)).first(), files(PieceType::Rook(Colour::White)).as_slice()) {
+                self.castling_files[0] = (*king, *kingside, *queenside);
+                self.castling = [true, true, false, false];
+            }
+        }
+        self.portals = lesson.portals;
+        self.fairies = HashMap::new();
+        self.shields = HashSet::new();
+        self.evolving = HashSet::new();
+        self.place_fairies();
+        self.place_shields();
+        self.double_used = (false, false);
+        self.double_armed = lesson.modi == Mods::DoubleMove;
+        self.spare_knights = (lesson.modi == Mods::SpareKnight, false);
+        self.teleport_used = (false, false);
+        self.teleporting = false;
+        self.start_fen = self.position_fen();
+        self.move_log = Vec::new();
+        self.promoted = HashSet::new();
+        self.position_history = Vec::new();
+        self.screen = ScreenState::GameScreen;
+        self.selected_pos = (0, 0);
+        self.highlighted_pos = Vec::new();
+    }
+
+    /// Leaves the tutorial and restores the game it interrupted.
+    fn end_tutorial(&mut self) {
+        if let Some(tutorial) = self.tutorial.take() {
+            self.restore(tutorial.saved);
+            self.screen = tutorial.screen;
+        }
+    }
+
+    /// Whether moving from `from` to `to` may be played, which in the tutorial only the lesson's move may.
+    fn lesson_allows(&self, from: (isize, isize), to: (isize, isize)) -> bool {
+        match &self.tutorial {
+            Some(tutorial) => {
+                let lesson = &lessons()[tutorial.lesson];
+                from == lesson.from && to == lesson.to
+            },
+            None => true,
+        }
+    }
+
+    /// Updates the ratings of the seated players after a game. With `rate_mods` on, every mod a side
+    /// held counts as `MOD_ELO` points in its favour when working out the result it was expected to get.
+    fn rate_game(&mut self, white: usize, black: usize, winner: Option<Colour>) {
+        let bonus = |mods: &Vec<String>| if self.rate_mods { mods.len() as f32 * MOD_ELO } else { 0.0 };
+        let strength = (self.profiles[white].rating + bonus(&self.game_mods[0]), self.profiles[black].rating + bonus(&self.game_mods[1]));
+        let score = match winner {
+            Some(Colour::White) => 1.0,
+            Some(Colour::Black) => 0.0,
+            None => 0.5,
+        };
+        let change = ELO_K * (score - expected_score(strength.0, strength.1));
+        self.profiles[white].rating += change;
+        self.profiles[black].rating -= change;
+        self.profiles[white].games += 1;
+        self.profiles[black].games += 1;
+        self.rating_change = Some(change);
+        self.save_profiles();
+    }
+
+    fn save_profiles(&self) {
+        let text = self.profiles.iter().map(|profile| profile.to_line() + "\n").collect::<String>();
+        if let Err(e) = std::fs::write(PROFILES_FILE, text) {
+            println!("Couldn't save {}: {}", PROFILES_FILE, e);
+        }
+    }
+
+    /// Seats the `i`th profile on the side it prefers, or the other one if that is taken,
+    /// or stands it up if it was already seated.
+    fn seat_profile(&mut self, i: usize) {
+        if let Some(seat) = self.seats.iter().position(|seat| *seat == Some(i)) {
+            self.seats[seat] = None;
+            return;
+        }
+        let preferred = if self.profiles[i].side == Colour::White { 0 } else { 1 };
+        let seat = if self.seats[preferred].is_some() && self.seats[1 - preferred].is_none() { 1 - preferred } else { preferred };
+        self.seats[seat] = Some(i);
+    }
+
+    /// Handles a click on the profile screen: the piece set and side boxes cycle the profile's
+    /// preferences, anywhere else on its row seats it.
+    fn profile_click(&mut self, x: f32, y: f32) {
+        let point = ggez::mint::Point2 { x: x, y: y };
+        for i in 0..self.profiles.len().min(PROFILE_ROWS) {
+            if profile_set_rect(i).contains(point) {
+                self.profiles[i].set = self.profiles[i].set.next();
+                self.save_profiles();
+            } else if profile_side_rect(i).contains(point) {
+                self.profiles[i].side = opposite(self.profiles[i].side);
+                self.save_profiles();
+            } else if profile_row_rect(i).contains(point) {
+                self.seat_profile(i);
+            }
+        }
+    }
+
+    /// Adds a profile under the name typed in, unless the name is taken.
+    fn add_profile(&mut self) {
+        let name = self.new_profile.trim().to_string();
+        self.new_profile = String::new();
+        if !name.is_empty() && !self.profiles.iter().any(|profile| profile.name == name) {
+            self.profiles.push(Profile::new(&name));
+            self.save_profiles();
+        }
+    }
+
+    /// Leaves the profile screen and starts a new series between the seated players, with a guest in any empty seat.
+    fn start_series(&mut self) {
+        for (seat, guest) in [0, 1].iter().zip(["White", "Black"].iter()) {
+            self.players[*seat] = match self.seats[*seat] {
+                Some(i) => self.profiles[i].name.clone(),
+                None => guest.to_string(),
+            };
+        }
+        self.series_start = unix_time();
+        self.wins = (0, 0);
+        self.screen = ScreenState::GameScreen;
+    }
+
+    /// How `col` is named on screen: just the colour for a guest known by it, the player's name and colour otherwise.
+    fn side_label(&self, col: Colour) -> String {
+        let name = &self.players[if col == Colour::White { 0 } else { 1 }];
+        if *name == format!("{:?}", col) {
+            name.clone()
+        } else {
+            format!("{} ({:?})", name, col)
+        }
+    }
+
+    /// Colour to draw the pieces of `col` with, from the piece set of the player on that side.
+    fn piece_tint(&self, col: Colour) -> Color {
+        match self.seats[if col == Colour::White { 0 } else { 1 }] {
+            Some(i) => self.profiles[i].set.tint(),
+            None => PieceSet::Classic.tint(),
+        }
+    }
+
+    /// Display names of the mods White and Black hold, for the match history.
+    fn held_mod_names(&self) -> [Vec<String>; 2] {
+        let names = |mods: &HashSet<Mods>, col: Colour| {
+            let mut names = mods.iter().map(|modi| modi.display_name(col)).collect::<Vec<String>>();
+            names.sort();
+            names
+        };
+        [names(&self.white_mods, Colour::White), names(&self.black_mods, Colour::Black)]
+    }
+
+    /// Puts each player on the other side, taking along their mods, banished mods, rerolls and score.
+    fn swap_colours(&mut self) {
+        let flip = |mods: &HashSet<Mods>| mods.iter().map(|modi| modi.flipped()).collect::<HashSet<Mods>>();
+        let (white_mods, black_mods) = (flip(&self.black_mods), flip(&self.white_mods));
+        self.white_mods = white_mods;
+        self.black_mods = black_mods;
+        let (white_banished, black_banished) = (flip(&self.black_banished), flip(&self.white_banished));
+        self.white_banished = white_banished;
+        self.black_banished = black_banished;
+        self.rerolls = (self.rerolls.1, self.rerolls.0);
+        self.wins = (self.wins.1, self.wins.0);
+        self.players.swap(0, 1);
+        self.seats.swap(0, 1);
+    }
+
+    /// Leaves the score or mod screen and starts the next game of the series,
+    /// with the players changing colours first if they alternate.
+    fn start_next_game(&mut self) {
+        if self.alternate && self.bughouse.is_none() {
+            self.swap_colours();
+        }
+        self.screen = ScreenState::GameScreen;
+        self.setup_board();
+        self.random_mods = Vec::new();
+    }
+
+    /// Turns the series variant on, or off again if it already is, before the first move. Only the
+    /// variant's own setup changes, so one-game mods, Chess960 back ranks and sabotage stay as they were.
+    fn toggle_series_variant(&mut self, variant: Variant) {
+        let old = self.series_variant;
+        self.series_variant = if old == variant { Variant::Standard } else { variant };
+        // a one-game variant mod decides this game, so the series variant starts with the next one
+        if self.variant != old {
+            return;
+        }
+        if self.variant == Variant::Standard {
+            self.variant_backup = Some(self.snapshot());
+        }
+        // every variant starts over from the standard setup
+        if let Some(backup) = self.variant_backup.clone() {
+            self.restore(backup);
+        }
+        if self.series_variant == Variant::Standard {
+            self.variant_backup = None;
+        }
+        self.variant = self.series_variant;
+        if self.variant != Variant::Standard {
+            // portals and fairies only exist in standard games
+            self.portals = None;
+            self.fairies = HashMap::new();
+        }
+        if self.variant == Variant::RacingKings {
+            self.board = load_position(RACING_KINGS_START, Colour::White);
+            self.castling = [false; 4];
+            self.shields = HashSet::new();
+            self.place_shields();
+        }
+        self.duck = None;
+        self.duck_pending = false;
+        self.start_fen = self.position_fen();
+    }
+
+    /// Sets up the starting position for the next game according to the mods held.
+    fn setup_board(&mut self) {
+        self.game_mods = self.held_mod_names();
+        self.variant_backup = None;
+        // variant mods only last for a single game
+        self.variant = self.series_variant;
+        for (modi, variant) in [(Mods::Antichess, Variant::Antichess), (Mods::RacingKings, Variant::RacingKings), (Mods::Duck, Variant::Duck)].iter() {
+            if self.white_mods.remove(modi) | self.black_mods.remove(modi) {
+                self.variant = *variant;
+            }
+        }
+        self.racing_finish = false;
+        self.duck = None;
+        self.duck_pending = false;
+        self.portals = None;
+        self.fairies = HashMap::new();
+        self.shields = HashSet::new();
+        self.evolving = HashSet::new();
+        self.undo_stack = Vec::new();
+        self.double_used = (false, false);
+        self.double_armed = false;
+        self.teleport_used = (false, false);
+        self.teleporting = false;
+        self.triple_check_counter = (0, 0);
+        self.board = Game::new();
+        self.castling = [true; 4];
+        self.castling_files = [(5, 8, 1); 2];
+        if let Some(bughouse) = self.bughouse.as_mut() {
+            let networked = bughouse.link.is_some();
+            let swapped = networked && bughouse.swapped;
+            *bughouse = Bughouse::new(bughouse.link.take(), swapped);
+            self.taken_black_pieces = Vec::new();
+            self.taken_white_pieces = Vec::new();
+        }
+        // captured pieces carry over to the next game, but an unused spare knight doesn't
+        if self.spare_knights.0 {
+            if let Some(i) = self.taken_black_pieces.iter().position(|piece| *piece == PieceType::Knight(Colour::Black)) {
+                self.taken_black_pieces.remove(i);
+            }
+        }
+        if self.spare_knights.1 {
+            if let Some(i) = self.taken_white_pieces.iter().position(|piece| *piece == PieceType::Knight(Colour::White)) {
+                self.taken_white_pieces.remove(i);
+            }
+        }
+        self.spare_knights = (false, false);
+        if self.variant == Variant::RacingKings {
+            self.board = load_position(RACING_KINGS_START, Colour::White);
+            self.castling = [false; 4];
+            self.start_fen = self.position_fen();
+            self.move_log = Vec::new();
+            self.promoted = HashSet::new();
+            self.position_history = Vec::new();
+            return;
+        }
+        for (i, col) in [Colour::White, Colour::Black].iter().enumerate() {
+            let mods = if *col == Colour::White { &self.white_mods } else { &self.black_mods };
+            if !mods.contains(&Mods::Chess960) {
+                continue;
+            }
+            let setup = unsafe {
+                let mut rng: rngs::StdRng = rand::SeedableRng::seed_from_u64(seed);
+                seed += 1;
+                rng.gen_range(0..960)
+            };
+            let rank = if *col == Colour::White { 1 } else { 8 };
+            let back_rank = chess960_back_rank(setup, *col);
+            for file in 1..9 {
+                self.board.board.insert(Position { file: file, rank: rank }, back_rank[file as usize - 1]);
+            }
+            let king = (1..9).find(|file| back_rank[*file as usize - 1] == PieceType::King(*col)).unwrap();
+            let rooks = (1..9).filter(|file| back_rank[*file as usize - 1] == PieceType::Rook(*col)).collect::<Vec<u8>>();
+            self.castling_files[i] = (king, rooks[1], rooks[0]);
+        }
+
+        for (i, col) in [Colour::White, Colour::Black].iter().enumerate() {
+            let mods = if *col == Colour::White { &self.white_mods } else { &self.black_mods };
+            let army = match mods.iter().find_map(|modi| if let Mods::Army(army) = modi { Some(*army) } else { None }) {
+                Some(army) => &armies()[army],
+                None => continue,
+            };
+
+            // clear the holder's home ranks and whatever else the army reaches, leaving the other army alone
+            let depth = army.ranks.len().max(2) as u8;
+            let home_ranks = if *col == Colour::White { 1..depth + 1 } else { 9 - depth..9 };
+            self.board.board.retain(|pos, _| !home_ranks.contains(&pos.rank));
+            for (row, rank) in army.ranks.iter().enumerate() {
+                let rank_number = if *col == Colour::White { row as u8 + 1 } else { 8 - row as u8 };
+                for (file, piece) in rank.iter().enumerate() {
+                    if let Some(piece) = piece {
+                        self.board.board.insert(Position { file: file as u8 + 1, rank: rank_number }, piece.type_as_colour(*col));
+                    }
+                }
+            }
+
+            // only an untouched back rank keeps its castling rights
+            let back_rank = if *col == Colour::White { 1 } else { 8 };
+            let standard_board = Game::new().board;
+            let standard = (1..9).all(|file| self.board.board.get(&Position { file: file, rank: back_rank }) == standard_board.get(&Position { file: file, rank: back_rank }));
+            if !standard {
+                self.castling[i * 2] = false;
+                self.castling[i * 2 + 1] = false;
+            }
+        }
+
+        self.place_material();
+        self.place_portals();
+        self.place_fairies();
+        self.place_shields();
+        self.start_fen = self.position_fen();
+        self.move_log = Vec::new();
+        self.promoted = HashSet::new();
+        self.position_history = Vec::new();
+    }
+
+    /// Adds and takes away starting material for the Extra Pawn, Spare Knight and Sabotage mods held.
+    fn place_material(&mut self) {
+        for (i, col) in [Colour::White, Colour::Black].iter().enumerate() {
+            let mods = if *col == Colour::White { self.white_mods.clone() } else { self.black_mods.clone() };
+            let third_rank = if *col == Colour::White { 3 } else { 6 };
+            for modi in mods.iter() {
+                match modi {
+                    Mods::ExtraPawn(file) => {
+                        self.board.board.entry(Position { file: *file, rank: third_rank }).or_insert(PieceType::Pawn(*col));
+                    },
+                    Mods::SpareKnight => {
+                        // the tray of the side dropping holds the pieces it took, so in the other colour
+                        if *col == Colour::White {
+                            self.taken_black_pieces.push(PieceType::Knight(Colour::Black));
+                            self.spare_knights.0 = true;
+                        } else {
+                            self.taken_white_pieces.push(PieceType::Knight(Colour::White));
+                            self.spare_knights.1 = true;
+                        }
+                    },
+                    Mods::Sabotage(piece) => {
+                        // sorted first, so the same seed always takes the same one
+                        let mut candidates = self.board.board.iter()
+                            .filter(|(_, other)| *other == piece)
+                            .map(|(pos, _)| (pos.file, pos.rank))
+                            .collect::<Vec<(u8, u8)>>();
+                        candidates.sort();
+                        let victim = if candidates.is_empty() {
+                            None
+                        } else {
+                            unsafe {
+                                let mut rng: rngs::StdRng = rand::SeedableRng::seed_from_u64(seed);
+                                seed += 1;
+                                Some(candidates[rng.gen_range(0..candidates.len())])
+                            }
+                        };
+                        if let Some((file, rank)) = victim {
+                            self.board.board.remove(&Position { file: file, rank: rank });
+                            let enemy = 1 - i;
+                            let (_, kingside_rook, queenside_rook) = self.castling_files[enemy];
+                            if *piece == PieceType::Rook(piece.colour()) && file == kingside_rook {
+                                self.castling[enemy * 2] = false;
+                            } else if *piece == PieceType::Rook(piece.colour()) && file == queenside_rook {
+                                self.castling[enemy * 2 + 1] = false;
+                            }
+                        }
+                    },
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Swaps each Fairy mod holder's base piece for its fairy, preferring the usual back rank squares.
+    fn place_fairies(&mut self) {
+        if self.variant != Variant::Standard {
+            return;
+        }
+        for col in [Colour::White, Colour::Black].iter() {
+            let mods = if *col == Colour::White { &self.white_mods } else { &self.black_mods };
+            let held = mods.iter().filter_map(|modi| if let Mods::Fairy(fairy) = modi { Some(*fairy) } else { None }).collect::<Vec<Fairy>>();
+            let back_rank = if *col == Colour::White { 1 } else { 8 };
+            for fairy in held {
+                let base = fairy.base(*col);
+                let free = |square: &(u8, u8)| self.board.board.get(&Position { file: square.0, rank: square.1 }) == Some(&base) && !self.fairies.contains_key(square);
+                let mut squares = fairy.home_files().iter().map(|file| (*file, back_rank)).collect::<Vec<(u8, u8)>>();
+                // the rest in a fixed order, so a seeded game places them the same way every time
+                let mut others = self.board.board.keys().map(|pos| (pos.file, pos.rank)).collect::<Vec<(u8, u8)>>();
+                others.sort();
+                squares.extend(others);
+                if let Some(square) = squares.into_iter().find(free) {
+                    self.fairies.insert(square, fairy);
+                }
+            }
+        }
+    }
+
+    /// Gives every piece of a Shield mod holder's shield type its shield.
+    fn place_shields(&mut self) {
+        for (pos, piece) in self.board.board.iter() {
+            let mods = if piece.colour() == Colour::White { &self.white_mods } else { &self.black_mods };
+            if mods.contains(&Mods::Shield(*piece)) {
+                self.shields.insert((pos.file, pos.rank));
+            }
+        }
+    }
+
+    /// The square of the piece taken by moving from `from` to `to`: the passed pawn for an en passant capture, else `to`.
+    fn captured_square(&self, from: (u8, u8), to: (u8, u8)) -> (u8, u8) {
+        let pawn = matches!(self.board.board.get(&Position { file: from.0, rank: from.1 }), Some(PieceType::Pawn(_)));
+        if pawn && from.0 != to.0 && !self.board.board.contains_key(&Position { file: to.0, rank: to.1 }) {
+            (to.0, from.1)
+        } else {
+            to
+        }
+    }
+
+    /// Whether moving from `from` to `to` hits an enemy piece whose shield is still up.
+    fn shield_blocks(&self, from: (u8, u8), to: (u8, u8)) -> bool {
+        let captured = self.captured_square(from, to);
+        match (self.board.board.get(&Position { file: from.0, rank: from.1 }), self.board.board.get(&Position { file: captured.0, rank: captured.1 })) {
+            (Some(piece), Some(target)) => piece.colour() != target.colour() && self.shields.contains(&captured),
+            _ => false,
+        }
+    }
+
+    /// Whether the king of `col` is attacked right now, by whatever rules the current game uses.
+    fn king_attacked(&self, col: Colour) -> bool {
+        if self.variant == Variant::Antichess || self.variant == Variant::Duck {
+            return false;
+        }
+        if self.atomic_game() {
+            return self.atomic_in_check(&self.board.board, col);
+        }
+        let king = self.board.board.iter().find(|(_, piece)| **piece == PieceType::King(col)).map(|(pos, _)| (pos.file, pos.rank));
+        king.map_or(false, |(file, rank)| square_attacked(&self.board.board, file, rank, opposite(col)))
+            || (self.layered() && self.extra_check(&self.board.board, &self.fairies, col))
+    }
+
+    /// Whether a capture bouncing off a shield is a legal move. Nothing moves, so it can't get a king out of check.
+    fn bounce_legal(&self, from: (u8, u8), to: (u8, u8)) -> bool {
+        let col = match self.board.board.get(&Position { file: from.0, rank: from.1 }) {
+            Some(piece) => piece.colour(),
+            None => return false,
+        };
+        !self.shield_blocks(from, to) || !self.king_attacked(col)
+    }
+
+    /// Whether the side to move has a legal move by library rules once captures that bounce off
+    /// shields while in check are ruled out, drops included.
+    fn shield_has_moves(&self) -> bool {
+        let col = self.board.active_color;
+        self.drop_escapes() || self.teleport_escapes() || self.board.board.iter()
+            .filter(|(_, piece)| piece.colour() == col)
+            .any(|(pos, _)| self.board.get_possible_moves(pos.to_string()).unwrap_or_default().into_iter()
+                .map(|mov| Position::from_string(mov).unwrap())
+                .any(|to| self.bounce_legal((pos.file, pos.rank), (to.file, to.rank))))
+    }
+
+    /// Plays a capture that hits a shield: the shield breaks, neither piece moves and the turn passes.
+    fn break_shield(&mut self, from: (u8, u8), to: (u8, u8)) {
+        let col = match self.board.board.get(&Position { file: from.0, rank: from.1 }) {
+            Some(piece) => piece.colour(),
+            None => return,
+        };
+        let captured = self.captured_square(from, to);
+        self.shields.remove(&captured);
+        self.shield_hit = Some(captured);
+        self.board.active_color = opposite(col);
+    }
+
+    /// Whether `col` holds a double move it hasn't spent yet this game.
+    fn double_charge(&self, col: Colour) -> bool {
+        let (mods, used) = if col == Colour::White { (&self.white_mods, self.double_used.0) } else { (&self.black_mods, self.double_used.1) };
+        mods.contains(&Mods::DoubleMove) && !used
+    }
+
+    /// Calls off or calls up the double move of `col`, which has to be the side to move and still have the charge.
+    /// Duck turns and bughouse boards already have their own turn order, so it can't be used there.
+    fn toggle_double_move(&mut self, col: Colour) {
+        if col != self.board.active_color || !self.double_charge(col) || self.variant == Variant::Duck || self.bughouse.is_some() || self.tutorial.is_some() {
+            return;
+        }
+        self.double_armed = !self.double_armed;
+        self.selected_pos = (0, 0);
+        self.highlighted_pos = Vec::new();
+    }
+
+    /// Whether playing the selected piece or tray piece to `to` would give check, which the first half of a double move may not.
+    fn first_move_checks(&self, to: (isize, isize)) -> bool {
+        let to = (to.0 as u8, to.1 as u8);
+        let col = self.board.active_color;
+        let piece = match self.selected_pos.1 {
+            9 => self.taken_black_pieces.get(self.selected_pos.0 as usize).map(|piece| piece.type_as_colour(Colour::White)),
+            10 => self.taken_white_pieces.get(self.selected_pos.0 as usize).map(|piece| piece.type_as_colour(Colour::Black)),
+            _ => {
+                let from = (self.selected_pos.0 as u8, self.selected_pos.1 as u8);
+                return !self.shield_blocks(from, to) && self.gives_check(from, to);
+            }
+        };
+        let mut theoretical_board = self.board.board.clone();
+        if let Some(piece) = piece {
+            theoretical_board.insert(Position { file: to.0, rank: to.1 }, piece);
+        }
+        theoretical_board.iter()
+            .find(|(_, other)| **other == PieceType::King(opposite(col)))
+            .map_or(false, |(pos, _)| square_attacked(&theoretical_board, pos.file, pos.rank, col))
+            || (self.layered() && self.extra_check(&theoretical_board, &self.fairies, opposite(col)))
+    }
+
+    /// Hands the turn on after `mover` has moved: to the other side, or back to `mover` if it called its double move.
+    /// Whatever the library or a hand-played move left in `active_color` is overwritten.
+    fn pass_turn(&mut self, mover: Colour) -> bool {
+        if self.double_armed && self.double_charge(mover) {
+            self.double_armed = false;
+            if mover == Colour::White {
+                self.double_used.0 = true;
+            } else {
+                self.double_used.1 = true;
+            }
+            self.board.active_color = mover;
+            return true;
+        }
+        self.double_armed = false;
+        self.board.active_color = opposite(mover);
+        false
+    }
+
+    /// Label of a held mod in the mod panel, with the charge left on a double move or teleport.
+    fn panel_label(&self, col: Colour, modi: &Mods) -> String {
+        if self.screen != ScreenState::GameScreen {
+            return modi.display_name(col);
+        }
+        let charge = match modi {
+            Mods::DoubleMove if self.double_armed && self.board.active_color == col => "armed",
+            Mods::DoubleMove if self.double_charge(col) => "ready, M",
+            Mods::Teleport if self.teleport_charge(col) => "ready, right-click King",
+            Mods::DoubleMove | Mods::Teleport => "used",
+            _ => return modi.display_name(col),
+        };
+        format!("{} ({})", modi.display_name(col), charge)
+    }
+
+    /// Whether `col` holds a king teleport it hasn't used yet this game.
+    fn teleport_charge(&self, col: Colour) -> bool {
+        let (mods, used) = if col == Colour::White { (&self.white_mods, self.teleport_used.0) } else { (&self.black_mods, self.teleport_used.1) };
+        mods.contains(&Mods::Teleport) && !used && self.variant != Variant::Antichess
+    }
+
+    /// Squares of the pieces the king of the side to move may swap places with, tried out on a copy of the
+    /// board so that neither king is left in check and no pawn lands on the first or last rank.
+    fn teleport_squares(&self) -> Vec<(u8, u8)> {
+        let col = self.board.active_color;
+        if !self.teleport_charge(col) || self.duck_pending {
+            return Vec::new();
+        }
+        let king = match self.board.board.iter().find(|(_, piece)| **piece == PieceType::King(col)) {
+            Some((pos, _)) => (pos.file, pos.rank),
+            None => return Vec::new(),
+        };
+        let mut squares = Vec::new();
+        for (pos, piece) in self.board.board.iter() {
+            if piece.colour() != col || (pos.file, pos.rank) == king {
+                continue;
+            }
+            // a pawn can't be sent to the first or last rank
+            if *piece == PieceType::Pawn(col) && (king.1 == 1 || king.1 == 8) {
+                continue;
+            }
+            let mut theoretical_board = self.board.board.clone();
+            theoretical_board.insert(Position { file: king.0, rank: king.1 }, *piece);
+            theoretical_board.insert(pos.clone(), PieceType::King(col));
+            let mut fairies = self.fairies.clone();
+            if let Some(fairy) = fairies.remove(&(pos.file, pos.rank)) {
+                fairies.insert(king, fairy);
+            }
+            let checked = |side: Colour| {
+                if self.atomic_game() {
+                    return self.atomic_in_check(&theoretical_board, side);
+                }
+                let square = theoretical_board.iter().find(|(_, other)| **other == PieceType::King(side)).map(|(pos, _)| (pos.file, pos.rank));
+                square.map_or(false, |(file, rank)| square_attacked(&theoretical_board, file, rank, opposite(side)))
+                    || (self.layered() && self.extra_check(&theoretical_board, &fairies, side))
+            };
+            if !checked(col) && !checked(opposite(col)) {
+                squares.push((pos.file, pos.rank));
+            }
+        }
+        squares
+    }
+
+    /// Whether the side to move can get out of a spot with a king teleport.
+    fn teleport_escapes(&self) -> bool {
+        !self.teleport_squares().is_empty()
+    }
+
+    /// Swaps the king of the side to move with its piece on `to`, using up the teleport.
+    fn teleport(&mut self, to: (u8, u8)) {
+        let col = self.board.active_color;
+        let king = match self.board.board.iter().find(|(_, piece)| **piece == PieceType::King(col)) {
+            Some((pos, _)) => (pos.file, pos.rank),
+            None => return,
+        };
+        let piece = match self.board.board.get(&Position { file: to.0, rank: to.1 }) {
+            Some(piece) => *piece,
+            None => return,
+        };
+        self.board.board.insert(Position { file: king.0, rank: king.1 }, piece);
+        self.board.board.insert(Position { file: to.0, rank: to.1 }, PieceType::King(col));
+        // the layers on the two squares trade places as well
+        let (fairy, shield, evolving, promoted) = (self.fairies.remove(&to), self.shields.remove(&to), self.evolving.remove(&to), self.promoted.remove(&to));
+        self.move_layers(king, to);
+        if let Some(fairy) = fairy {
+            self.fairies.insert(king, fairy);
+        }
+        if shield {
+            self.shields.insert(king);
+        }
+        if evolving {
+            self.evolving.insert(king);
+        }
+        if promoted {
+            self.promoted.insert(king);
+        }
+        self.update_castling((king.0 as isize, king.1 as isize), (to.0 as isize, to.1 as isize));
+        if col == Colour::White {
+            self.teleport_used.0 = true;
+        } else {
+            self.teleport_used.1 = true;
+        }
+        self.board.active_color = opposite(col);
+        self.log_move(format!("K<>{} {{teleport}}", Position { file: to.0, rank: to.1 }.to_string()));
+        self.end_move();
+        if self.variant == Variant::Duck {
+            self.duck_pending = true;
+        }
+    }
+
+    /// Drops the fairy, shield and evolution layers from the square `at`, whose piece is gone.
+    fn clear_layers(&mut self, at: (u8, u8)) {
+        self.fairies.remove(&at);
+        self.shields.remove(&at);
+        self.evolving.remove(&at);
+    }
+
+    /// Moves the fairy, shield and evolution layers along with a move from `from` to `to`, dropping whatever was captured there.
+    fn move_layers(&mut self, from: (u8, u8), to: (u8, u8)) {
+        self.clear_layers(to);
+        if let Some(fairy) = self.fairies.remove(&from) {
+            self.fairies.insert(to, fairy);
+        }
+        if self.shields.remove(&from) {
+            self.shields.insert(to);
+        }
+        if self.evolving.remove(&from) {
+            self.evolving.insert(to);
+        }
+    }
+
+    /// Opens two linked portals on empty middle squares if anyone holds a Portal mod.
+    fn place_portals(&mut self) {
+        let held = self.white_mods.iter().chain(self.black_mods.iter()).any(|modi| if let Mods::Portal(_) = modi { true } else { false });
+        self.portals = None;
+        if !held || self.variant != Variant::Standard {
+            return;
+        }
+        let free = (1..9).flat_map(|file| (3..7).map(move |rank| (file, rank)))
+            .filter(|(file, rank)| !self.board.board.contains_key(&Position { file: *file, rank: *rank }))
+            .collect::<Vec<(u8, u8)>>();
+        if free.len() < 2 {
+            return;
+        }
+        unsafe {
+            let mut rng: rngs::StdRng = rand::SeedableRng::seed_from_u64(seed);
+            seed += 1;
+            let first = rng.gen_range(0..free.len());
+            let mut second = rng.gen_range(0..free.len() - 1);
+            if second >= first {
+                second += 1;
+            }
+            self.portals = Some((free[first], free[second]));
+        }
+    }
+
+    /// Switches which bughouse board is being played on a shared machine.
+    fn swap_boards(&mut self) {
+        let bughouse = match self.bughouse.as_mut() {
+            Some(bughouse) => bughouse,
+            None => return,
+        };
+        std::mem::swap(&mut self.board, &mut bughouse.board);
+        std::mem::swap(&mut self.taken_black_pieces, &mut bughouse.taken_black_pieces);
+        std::mem::swap(&mut self.taken_white_pieces, &mut bughouse.taken_white_pieces);
+        std::mem::swap(&mut self.move_log, &mut bughouse.move_log);
+        std::mem::swap(&mut self.castling, &mut bughouse.castling);
+        std::mem::swap(&mut self.promoted, &mut bughouse.promoted);
+        std::mem::swap(&mut self.position_history, &mut bughouse.position_history);
+        bughouse.clocks.swap(0, 1);
+        bughouse.swapped = !bughouse.swapped;
+        bughouse.received = (self.taken_black_pieces.len(), self.taken_white_pieces.len());
+        self.selected_pos = (0, 0);
+        self.highlighted_pos = Vec::new();
+    }
+
+    /// Swaps the partner board and its trays in or out, leaving the rest of the game as it is.
+    fn swap_partner_position(&mut self) {
+        if let Some(bughouse) = self.bughouse.as_mut() {
+            std::mem::swap(&mut self.board, &mut bughouse.board);
+            std::mem::swap(&mut self.taken_black_pieces, &mut bughouse.taken_black_pieces);
+            std::mem::swap(&mut self.taken_white_pieces, &mut bughouse.taken_white_pieces);
+        }
+    }
+
+    /// Whether the side to move on the partner board is mated, with no drop to get out of it.
+    fn partner_mated(&mut self) -> bool {
+        self.swap_partner_position();
+        let mated = self.board.get_game_state() == GameState::CheckMate && !self.drop_escapes();
+        self.swap_partner_position();
+        mated
+    }
+
+    /// Whether a hosted bughouse game is still waiting for the partner machine to connect.
+    fn waiting_for_partner(&self) -> bool {
+        self.bughouse.as_ref().and_then(|bughouse| bughouse.link.as_ref()).map_or(false, |link| !link.connected())
+    }
+
+    /// Runs the bughouse clocks, hands captured pieces to the partner and talks to the partner machine.
+    fn update_bughouse(&mut self, elapsed: f32) {
+        // on a shared machine, mate on the board not being played on ends the game as well
+        let hot_seat = self.bughouse.as_ref().map_or(false, |bughouse| bughouse.link.is_none());
+        let partner_mate = if hot_seat && self.partner_mated() {
+            self.bughouse.as_ref().map(|bughouse| bughouse.board.active_color)
+        } else {
+            None
+        };
+        let mut bughouse = match self.bughouse.take() {
+            Some(bughouse) => bughouse,
+            None => return,
+        };
+        // a host's clocks only start once the partner machine is there
+        if let Some(link) = bughouse.link.as_mut() {
+            link.accept();
+            if !link.connected() {
+                self.bughouse = Some(bughouse);
+                return;
+            }
+        }
+        let mut result = None;
+
+        // both boards run at once, each on the clock of its side to move
+        let partner_turn = bughouse.board.active_color;
+        for (i, turn) in [self.board.active_color, partner_turn].iter().enumerate() {
+            let clock = if *turn == Colour::White { &mut bughouse.clocks[i].0 } else { &mut bughouse.clocks[i].1 };
+            *clock -= elapsed;
+            // a flag on the partner board is for the partner machine to call
+            if *clock <= 0.0 && (i == 0 || bughouse.link.is_none()) {
+                *clock = 0.0;
+                result = Some(Some(if i == 0 { opposite(*turn) } else { *turn }));
+            }
+        }
+
+        // whatever was captured here since the last update goes to the partner
+        let captured = self.taken_black_pieces.split_off(bughouse.received.0.min(self.taken_black_pieces.len())).into_iter()
+            .map(|piece| piece.type_as_colour(Colour::White))
+            .chain(self.taken_white_pieces.split_off(bughouse.received.1.min(self.taken_white_pieces.len())).into_iter()
+                .map(|piece| piece.type_as_colour(Colour::Black)))
+            .collect::<Vec<PieceType>>();
+        bughouse.received = (self.taken_black_pieces.len(), self.taken_white_pieces.len());
+        match bughouse.link.as_mut() {
+            Some(link) => {
+                if !captured.is_empty() {
+                    link.send(&format!("pass {}", captured.iter().map(|piece| piece_char(*piece)).collect::<String>()));
+                }
+            },
+            None => {
+                for piece in captured {
+                    if piece.colour() == Colour::White {
+                        bughouse.taken_white_pieces.push(piece);
+                    } else {
+                        bughouse.taken_black_pieces.push(piece);
+                    }
+                }
+            },
+        }
+
+        if let Some(link) = bughouse.link.as_mut() {
+            if self.move_log.len() != bughouse.sent_moves {
+                bughouse.sent_moves = self.move_log.len();
+                link.send(&format!("board {} {} {}", bughouse.clocks[0].0, bughouse.clocks[0].1, self.position_fen()));
+            }
+            for line in link.receive() {
+                let words = line.split_whitespace().collect::<Vec<&str>>();
+                match words.as_slice() {
+                    ["board", white_clock, black_clock, position, to_move, ..] => {
+                        let mut parts = position.trim_end_matches(']').split('[');
+                        let placement = parts.next().unwrap_or_default();
+                        let pockets = parts.next().unwrap_or_default();
+                        bughouse.board = load_position(placement, if *to_move == "b" { Colour::Black } else { Colour::White });
+                        bughouse.taken_black_pieces = pockets.chars().filter(|c| c.is_ascii_uppercase()).filter_map(piece_from_char).map(|piece| piece.type_as_colour(Colour::Black)).collect();
+                        bughouse.taken_white_pieces = pockets.chars().filter(|c| c.is_ascii_lowercase()).filter_map(piece_from_char).map(|piece| piece.type_as_colour(Colour::White)).collect();
+                        bughouse.clocks[1] = (white_clock.parse().unwrap_or(0.0), black_clock.parse().unwrap_or(0.0));
+                    },
+                    ["pass", pieces] => {
+                        for piece in pieces.chars().filter_map(piece_from_char) {
+                            if piece.colour() == Colour::White {
+                                self.taken_white_pieces.push(piece);
+                            } else {
+                                self.taken_black_pieces.push(piece);
+                            }
+                        }
+                        bughouse.received = (self.taken_black_pieces.len(), self.taken_white_pieces.len());
+                    },
+                    ["result", team] => {
+                        let winner = match *team {
+                            "w" => Some(Colour::White),
+                            "b" => Some(Colour::Black),
+                            _ => None,
+                        };
+                        self.bughouse = Some(bughouse);
+                        if self.screen == ScreenState::GameScreen {
+                            self.record_result(winner, Termination::PartnerBoard);
+                        }
+                        return;
+                    },
+                    _ => link.status = format!("Unknown message from the partner board: {}", line),
+                }
+            }
+        }
+
+        self.bughouse = Some(bughouse);
+        if let Some(winner) = result {
+            self.end_game(winner, Termination::Time);
+        } else if let Some(mated) = partner_mate {
+            // whoever mated on the partner board has a partner playing the mated colour here
+            self.end_game(Some(mated), Termination::Checkmate);
+        }
+    }
+
+    /// Moves the piece on `from` can make through the portals, if it is its holder's portal piece.
+    /// Stepping onto a portal square comes out of the other one, and sliders carry on from there.
+    fn portal_moves(&self, board: &HashMap<Position, PieceType>, from: (u8, u8)) -> Vec<(u8, u8)> {
+        let (portal_a, portal_b) = match self.portals {
+            Some(portals) => portals,
+            None => return Vec::new(),
+        };
+        let piece = match board.get(&Position { file: from.0, rank: from.1 }) {
+            Some(piece) => *piece,
+            None => return Vec::new(),
+        };
+        let col = piece.colour();
+        let mods = if col == Colour::White { &self.white_mods } else { &self.black_mods };
+        if !mods.contains(&Mods::Portal(piece)) {
+            return Vec::new();
+        }
+        let on_board = |file: i8, rank: i8| file >= 1 && file <= 8 && rank >= 1 && rank <= 8;
+        let steps: Vec<(i8, i8)> = match piece {
+            PieceType::Pawn(_) => vec![(0, if col == Colour::White { 1 } else { -1 })],
+            PieceType::Knight(_) => vec![(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)],
+            PieceType::Rook(_) => vec![(1, 0), (-1, 0), (0, 1), (0, -1)],
+            PieceType::Bishop(_) => vec![(1, 1), (1, -1), (-1, 1), (-1, -1)],
+            _ => vec![(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)],
+        };
+        let sliding = match piece {
+            PieceType::Rook(_) | PieceType::Bishop(_) | PieceType::Queen(_) => true,
+            _ => false,
+        };
+
+        let mut moves = Vec::new();
+        for (df, dr) in steps.iter() {
+            let (mut file, mut rank) = (from.0 as i8, from.1 as i8);
+            let mut teleported = false;
+            loop {
+                file += df;
+                rank += dr;
+                if !on_board(file, rank) {
+                    break;
+                }
+                let square = (file as u8, rank as u8);
+                if !teleported && (square == portal_a || square == portal_b) && !board.contains_key(&Position { file: square.0, rank: square.1 }) {
+                    let exit = if square == portal_a { portal_b } else { portal_a };
+                    file = exit.0 as i8;
+                    rank = exit.1 as i8;
+                    teleported = true;
+                    match board.get(&Position { file: exit.0, rank: exit.1 }) {
+                        Some(other) if other.colour() == col => break,
+                        // pawns only capture diagonally, so they can't take on the way out
+                        Some(_) if piece == PieceType::Pawn(col) => break,
+                        Some(_) => {
+                            moves.push(exit);
+                            break;
+                        },
+                        None => moves.push(exit),
+                    }
+                } else if board.contains_key(&Position { file: square.0, rank: square.1 }) {
+                    if teleported && board[&Position { file: square.0, rank: square.1 }].colour() != col && piece != PieceType::Pawn(col) {
+                        moves.push(square);
+                    }
+                    break;
+                } else if teleported {
+                    moves.push(square);
+                }
+                if !sliding {
+                    break;
+                }
+            }
+        }
+        moves.retain(|to| *to != from);
+        moves
+    }
+
+    /// Moves the library doesn't know about for the piece on `from`: through the portals, and a fairy
+    /// piece's knight jumps. `fairies` says where the fairy pieces stand on `board`.
+    fn extra_moves(&self, board: &HashMap<Position, PieceType>, fairies: &HashMap<(u8, u8), Fairy>, from: (u8, u8)) -> Vec<(u8, u8)> {
+        let mut moves = self.portal_moves(board, from);
+        if let (true, Some(piece)) = (fairies.contains_key(&from), board.get(&Position { file: from.0, rank: from.1 })) {
+            let mut jumps = board.clone();
+            jumps.insert(Position { file: from.0, rank: from.1 }, PieceType::Knight(piece.colour()));
+            for to in piece_moves(&jumps, from.0, from.1) {
+                if !moves.contains(&to) {
+                    moves.push(to);
+                }
+            }
+        }
+        moves
+    }
+
+    /// Whether the king of `col` can be taken on `board` by a move the library doesn't know about.
+    fn extra_check(&self, board: &HashMap<Position, PieceType>, fairies: &HashMap<(u8, u8), Fairy>, col: Colour) -> bool {
+        let king = match board.iter().find(|(_, piece)| **piece == PieceType::King(col)) {
+            Some((pos, _)) => (pos.file, pos.rank),
+            None => return false,
+        };
+        self.extra_attacked(board, fairies, king, opposite(col))
+    }
+
+    /// Whether a piece of colour `by` reaches `square` on `board` by a move the library doesn't know about.
+    fn extra_attacked(&self, board: &HashMap<Position, PieceType>, fairies: &HashMap<(u8, u8), Fairy>, square: (u8, u8), by: Colour) -> bool {
+        board.iter()
+            .filter(|(_, piece)| piece.colour() == by)
+            .any(|(pos, _)| self.extra_moves(board, fairies, (pos.file, pos.rank)).contains(&square))
+    }
+
+    /// Whether portals or fairy pieces are in play, so legality is decided here on top of the library.
+    fn layered(&self) -> bool {
+        self.portals.is_some() || !self.fairies.is_empty()
+    }
+
+    /// Legal moves for the piece on `from` when portals or fairies are in play: the library's moves that
+    /// don't leave the king open to an extra attack, plus extra moves that don't leave it in check at all.
+    fn layered_legal_moves(&self, from: (u8, u8)) -> Vec<(u8, u8)> {
+        let col = match self.board.board.get(&Position { file: from.0, rank: from.1 }) {
+            Some(piece) => piece.colour(),
+            None => return Vec::new(),
+        };
+        let after = |to: (u8, u8)| {
+            let mut theoretical_board = self.board.board.clone();
+            if let Some(piece) = theoretical_board.remove(&Position { file: from.0, rank: from.1 }) {
+                theoretical_board.insert(Position { file: to.0, rank: to.1 }, piece);
+            }
+            let mut fairies = self.fairies.clone();
+            fairies.remove(&to);
+            if let Some(fairy) = fairies.remove(&from) {
+                fairies.insert(to, fairy);
+            }
+            (theoretical_board, fairies)
+        };
+        let mut moves = Vec::new();
+        for mov in self.board.get_possible_moves(Position { file: from.0, rank: from.1 }.to_string()).unwrap_or_default() {
+            let to = Position::from_string(mov).unwrap();
+            let (theoretical_board, fairies) = after((to.file, to.rank));
+            if self.shield_blocks(from, (to.file, to.rank)) {
+                if self.bounce_legal(from, (to.file, to.rank)) {
+                    moves.push((to.file, to.rank));
+                }
+            } else if !self.extra_check(&theoretical_board, &fairies, col) {
+                moves.push((to.file, to.rank));
+            }
+        }
+        for to in self.extra_moves(&self.board.board, &self.fairies, from) {
+            let (theoretical_board, fairies) = after(to);
+            let king = theoretical_board.iter().find(|(_, piece)| **piece == PieceType::King(col)).map(|(pos, _)| (pos.file, pos.rank));
+            let attacked = king.map_or(false, |(file, rank)| square_attacked(&theoretical_board, file, rank, opposite(col)));
+            if self.shield_blocks(from, to) {
+                if !moves.contains(&to) && self.bounce_legal(from, to) {
+                    moves.push(to);
+                }
+            } else if !moves.contains(&to) && !attacked && !self.extra_check(&theoretical_board, &fairies, col) {
+                moves.push(to);
+            }
+        }
+        moves
+    }
+
+    /// Whether the side to move has any legal move with portals or fairies in play, drops included.
+    fn layered_has_moves(&self) -> bool {
+        let col = self.board.active_color;
+        self.drop_escapes() || self.teleport_escapes() || self.board.board.iter()
+            .filter(|(_, piece)| piece.colour() == col)
+            .any(|(pos, _)| !self.layered_legal_moves((pos.file, pos.rank)).is_empty())
+    }
+
+    /// Whether the side to move is in check, by a move the library knows or not.
+    fn layered_in_check(&mut self) -> bool {
+        let col = self.board.active_color;
+        self.board.get_game_state() == GameState::Check || self.board.get_game_state() == GameState::CheckMate || self.extra_check(&self.board.board, &self.fairies, col)
+    }
+
+    /// Resolves the mod hooks of a capture by the piece on `from` onto `to`, see `Capture`.
+    fn capture_hooks(&self, from: (u8, u8), to: (u8, u8)) -> Capture {
+        let capturer = self.board.board[&Position { file: from.0, rank: from.1 }];
+        let mods = if capturer.colour() == Colour::White { &self.white_mods } else { &self.black_mods };
+        resolve_capture(mods, capturer, self.shield_blocks(from, to), self.shields.contains(&from), self.evolving.contains(&from))
+    }
+
+    /// Runs the hooks that follow a capture of `target` from `from` onto `to` once the capturer has moved
+    /// (or stayed behind, when sniping): explosions, evolution and then extinction.
+    fn after_capture(&mut self, hooks: Capture, from: (u8, u8), to: (u8, u8), target: PieceType) {
+        if hooks.explodes {
+            self.explode(to, !hooks.sniper);
+        }
+        if hooks.evolves {
+            self.evolve(if hooks.sniper { from } else { to });
+        }
+        self.extinct(target);
+    }
+
+    /// Ends the game if `taken` was the last of its kind and the other side holds Extinction on it.
+    fn extinct(&mut self, taken: PieceType) {
+        let holder = opposite(taken.colour());
+        let mods = if holder == Colour::White { &self.white_mods } else { &self.black_mods };
+        if mods.contains(&Mods::Extinction(taken)) && !self.board.board.values().any(|piece| *piece == taken) && self.screen == ScreenState::GameScreen {
+            self.end_game(Some(holder), Termination::Extinction);
+        }
+    }
+
+    /// Upgrades the piece on `at` by one step, after which it keeps evolving whatever it has become.
+    fn evolve(&mut self, at: (u8, u8)) {
+        let piece = match self.board.board.get(&Position { file: at.0, rank: at.1 }) {
+            Some(piece) => *piece,
+            None => return,
+        };
+        if let Some(next) = evolved(piece) {
+            self.board.board.insert(Position { file: at.0, rank: at.1 }, next.type_as_colour(piece.colour()));
+            self.evolving.insert(at);
+            // an evolved pawn is still a pawn as far as crazyhouse trays go
+            if piece == PieceType::Pawn(piece.colour()) {
+                self.promoted.insert(at);
+            }
+            self.evolution = Some(next);
+        }
+    }
+
+    /// Whether `piece` explodes when it captures.
+    fn atomic_piece(&self, piece: PieceType) -> bool {
+        let mods = if piece.colour() == Colour::White { &self.white_mods } else { &self.black_mods };
+        mods.contains(&Mods::Atomic(piece))
+    }
+
+    /// Whether `col` plays by atomic rules, holding at least one Atomic mod.
+    fn plays_atomic(&self, col: Colour) -> bool {
+        let mods = if col == Colour::White { &self.white_mods } else { &self.black_mods };
+        mods.iter().any(|modi| if let Mods::Atomic(_) = modi { true } else { false })
+    }
+
+    /// Whether the current game has explosions in it, so legality is decided here instead of by the library.
+    fn atomic_game(&self) -> bool {
+        self.variant == Variant::Standard && (self.plays_atomic(Colour::White) || self.plays_atomic(Colour::Black))
+    }
+
+    /// Whether the king of `col` is in check on `board` by atomic rules. Exploding pieces can't take a king
+    /// touching their own, as the blast would take theirs too, and kings never check each other.
+    fn atomic_in_check(&self, board: &HashMap<Position, PieceType>, col: Colour) -> bool {
+        let find = |king: PieceType| board.iter().find(|(_, piece)| **piece == king).map(|(pos, _)| (pos.file as i8, pos.rank as i8));
+        let (own, enemy) = match (find(PieceType::King(col)), find(PieceType::King(opposite(col)))) {
+            (Some(own), Some(enemy)) => (own, enemy),
+            _ => return false,
+        };
+        let mut attackers = board.clone();
+        if (own.0 - enemy.0).abs() <= 1 && (own.1 - enemy.1).abs() <= 1 {
+            attackers.retain(|_, piece| piece.colour() == col || (*piece != PieceType::King(opposite(col)) && !self.atomic_piece(*piece)));
+        }
+        square_attacked(&attackers, own.0 as u8, own.1 as u8, opposite(col))
+    }
+
+    /// Whether moving from `from` to `to` is legal by atomic rules: kings of an atomic side don't capture,
+    /// no blast may take your own king, and blowing up the enemy king is always allowed.
+    fn atomic_legal(&self, from: (u8, u8), to: (u8, u8)) -> bool {
+        let mut board = self.board.board.clone();
+        let piece = match board.remove(&Position { file: from.0, rank: from.1 }) {
+            Some(piece) => piece,
+            None => return false,
+        };
+        let col = piece.colour();
+        let capture = board.insert(Position { file: to.0, rank: to.1 }, piece).is_some();
+        if capture && piece == PieceType::King(col) && self.plays_atomic(col) {
+            return false;
+        }
+        // a shielded target stops the capture, so nothing explodes
+        if self.shield_blocks(from, to) {
+            return self.bounce_legal(from, to);
+        }
+        if capture && self.atomic_piece(piece) {
+            for (file, rank) in blast_squares(&board, to, true) {
+                board.remove(&Position { file: file, rank: rank });
+            }
+        }
+        if !board.values().any(|other| *other == PieceType::King(col)) {
+            return false;
+        }
+        if !board.values().any(|other| *other == PieceType::King(opposite(col))) {
+            return true;
+        }
+        !self.atomic_in_check(&board, col)
+    }
+
+    /// Legal destinations for the piece on `from` in an atomic game, including king moves next to the
+    /// enemy king that the library refuses.
+    fn atomic_moves(&self, from: (u8, u8)) -> Vec<(u8, u8)> {
+        let mut moves = self.board.get_possible_moves(Position { file: from.0, rank: from.1 }.to_string()).unwrap_or_default().into_iter()
+            .map(|mov| Position::from_string(mov).unwrap())
+            .map(|pos| (pos.file, pos.rank))
+            .collect::<Vec<(u8, u8)>>();
+        for to in piece_moves(&self.board.board, from.0, from.1) {
+            if !moves.contains(&to) {
+                moves.push(to);
+            }
+        }
+        moves.into_iter().filter(|to| self.atomic_legal(from, *to)).collect()
+    }
+
+    /// Whether the side to move has any legal atomic move, drops included.
+    fn atomic_has_moves(&self) -> bool {
+        let col = self.board.active_color;
+        self.drop_escapes() || self.teleport_escapes() || self.board.board.iter()
+            .filter(|(_, piece)| piece.colour() == col)
+            .any(|(pos, _)| !self.atomic_moves((pos.file, pos.rank)).is_empty())
+    }
+
+    /// Blows up the square `at` and every non-pawn piece around it, the capturer too unless it stayed behind.
+    fn explode(&mut self, at: (u8, u8), with_capturer: bool) {
+        self.blast = blast_squares(&self.board.board, at, with_capturer);
+        for square in self.blast.clone().iter() {
+            // a shield soaks up the blast, and is gone afterwards
+            if self.shields.remove(square) {
+                continue;
+            }
+            if let Some(piece) = self.board.board.remove(&Position { file: square.0, rank: square.1 }) {
+                let taken = if self.promoted.remove(square) { PieceType::Pawn(piece.colour()) } else { piece };
+                match taken.colour() {
+                    Colour::White => self.taken_white_pieces.push(taken),
+                    Colour::Black => self.taken_black_pieces.push(taken),
+                }
+                self.extinct(piece);
+            }
+        }
+        self.explosions.push((at, EXPLOSION_TIME));
+        for col in [Colour::White, Colour::Black].iter() {
+            if !self.board.board.values().any(|piece| *piece == PieceType::King(*col)) {
+                self.end_game(Some(opposite(*col)), Termination::Explosion);
+            }
+        }
+    }
+
+    /// Squares the king of `col` has to reach to win, if any.
+    fn goal_squares(&self, col: Colour) -> Vec<(u8, u8)> {
+        let mods = if col == Colour::White { &self.white_mods } else { &self.black_mods };
+        if self.variant == Variant::RacingKings {
+            (1..9).map(|file| (file, 8)).collect()
+        } else if mods.contains(&Mods::KingOfTheHill) {
+            vec![(4, 4), (5, 4), (4, 5), (5, 5)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Whether the king of `col` stands on one of its goal squares.
+    fn king_on_goal(&self, col: Colour) -> bool {
+        self.goal_squares(col).iter().any(|(file, rank)| self.board.board.get(&Position { file: *file, rank: *rank }) == Some(&PieceType::King(col)))
+    }
+
+    /// Ends the game once a king has reached its goal. In Racing Kings Black
+    /// still gets one reply to draw by reaching the last rank as well.
+    fn check_goals(&mut self) {
+        let (white, black) = (self.king_on_goal(Colour::White), self.king_on_goal(Colour::Black));
+        if self.variant != Variant::RacingKings {
+            if white {
+                self.end_game(Some(Colour::White), Termination::Hill);
+            } else if black {
+                self.end_game(Some(Colour::Black), Termination::Hill);
+            }
+            return;
+        }
+
+        if white && black {
+            self.end_game(None, Termination::Race);
+        } else if black {
+            self.end_game(Some(Colour::Black), Termination::Race);
+        } else if white && self.board.active_color == Colour::White {
+            // Black had its reply and didn't make it
+            self.end_game(Some(Colour::White), Termination::Race);
+        } else if white && !self.racing_finish {
+            let mut can_reach = false;
+            if let Some(pos) = self.board.board.iter().find(|(_, piece)| **piece == PieceType::King(Colour::Black)).map(|(pos, _)| pos.clone()) {
+                for mov in self.board.get_possible_moves(pos.to_string()).unwrap_or_default() {
+                    let to = Position::from_string(mov).unwrap();
+                    if to.rank == 8 && !self.gives_check((pos.file, pos.rank), (to.file, to.rank)) {
+                        can_reach = true;
+                    }
+                }
+            }
+            if can_reach {
+                self.racing_finish = true;
+            } else {
+                self.end_game(Some(Colour::White), Termination::Race);
+            }
+        }
+    }
+
+    /// Whether moving the piece on `from` to `to` would check the opposing king.
+    fn gives_check(&self, from: (u8, u8), to: (u8, u8)) -> bool {
+        let mut theoretical_board = self.board.board.clone();
+        let piece = match theoretical_board.remove(&Position { file: from.0, rank: from.1 }) {
+            Some(piece) => piece,
+            None => return false,
+        };
+        theoretical_board.insert(Position { file: to.0, rank: to.1 }, piece);
+        let mut fairies = self.fairies.clone();
+        fairies.remove(&to);
+        if let Some(fairy) = fairies.remove(&from) {
+            fairies.insert(to, fairy);
+        }
+        let enemy_king = PieceType::King(opposite(piece.colour()));
+        theoretical_board.iter()
+            .find(|(_, other)| **other == enemy_king)
+            .map_or(false, |(pos, _)| square_attacked(&theoretical_board, pos.file, pos.rank, piece.colour()))
+            || (self.layered() && self.extra_check(&theoretical_board, &fairies, opposite(piece.colour())))
+    }
+
+    /// Antichess moves for the piece on `from`, where captures are compulsory and kings may be taken.
+    fn antichess_moves(&self, from: (u8, u8)) -> Vec<(u8, u8)> {
+        let col = self.board.active_color;
+        let capture = |to: &(u8, u8)| self.board.board.get(&Position { file: to.0, rank: to.1 }).map_or(false, |piece| piece.colour() != col);
+        let must_capture = self.board.board.iter()
+            .filter(|(_, piece)| piece.colour() == col)
+            .any(|(pos, _)| piece_moves(&self.board.board, pos.file, pos.rank).iter().any(capture));
+        piece_moves(&self.board.board, from.0, from.1).into_iter()
+            .filter(|to| !must_capture || capture(to))
+            .collect()
+    }
+
+    /// Whether the side to move has any antichess move, drops included.
+    fn antichess_has_moves(&self) -> bool {
+        let col = self.board.active_color;
+        let can_drop = !self.droppable(col).is_empty();
+        can_drop || self.board.board.iter()
+            .filter(|(_, piece)| piece.colour() == col)
+            .any(|(pos, _)| !piece_moves(&self.board.board, pos.file, pos.rank).is_empty())
+    }
+
+    /// The board as the side to move sees it, with the duck standing in as one of its own pawns
+    /// so that it blocks every piece and can't be captured.
+    fn duck_board(&self) -> HashMap<Position, PieceType> {
+        let mut board = self.board.board.clone();
+        if let Some((file, rank)) = self.duck {
+            board.insert(Position { file: file, rank: rank }, PieceType::Pawn(self.board.active_color));
+        }
+        board
+    }
+
+    /// Duck chess moves for the piece on `from`. There is no check, so a king may walk into an attack.
+    fn duck_moves(&self, from: (u8, u8)) -> Vec<(u8, u8)> {
+        let col = self.board.active_color;
+        if self.board.board.get(&Position { file: from.0, rank: from.1 }).map_or(true, |piece| piece.colour() != col) {
+            return Vec::new();
+        }
+        let mut moves = piece_moves(&self.duck_board(), from.0, from.1);
+        if self.board.board[&Position { file: from.0, rank: from.1 }] == PieceType::King(col) {
+            let (_, kingside_rook, queenside_rook) = self.castling_files[if col == Colour::White { 0 } else { 1 }];
+            for (kingside, rook) in [(true, kingside_rook), (false, queenside_rook)].iter() {
+                if self.can_castle(col, *kingside) {
+                    moves.push((*rook, from.1));
+                }
+            }
+        }
+        moves
+    }
+
+    /// Whether the side to move has any duck chess move, drops included.
+    fn duck_has_moves(&self) -> bool {
+        let col = self.board.active_color;
+        let can_drop = !self.droppable(col).is_empty();
+        can_drop || self.board.board.iter()
+            .filter(|(_, piece)| piece.colour() == col)
+            .any(|(pos, _)| !self.duck_moves((pos.file, pos.rank)).is_empty())
+    }
+
+    /// Puts the duck on an empty square other than the one it stands on, ending the mover's turn.
+    fn place_duck(&mut self, square: (u8, u8)) {
+        if square.0 < 1 || square.0 > 8 || square.1 < 1 || square.1 > 8 {
+            return;
+        }
+        if self.duck == Some(square) || self.board.board.contains_key(&Position { file: square.0, rank: square.1 }) {
+            return;
+        }
+        self.duck = Some(square);
+        self.duck_pending = false;
+        // PGN has no duck moves, so the square goes into a comment after the move
+        if let Some(last) = self.move_log.last_mut() {
+            last.push_str(&format!(" {{@{}}}", Position { file: square.0, rank: square.1 }.to_string()));
+        }
+    }
+
+    /// Tray pieces `col` is allowed to drop, as they sit in its tray.
+    fn droppable(&self, col: Colour) -> Vec<PieceType> {
+        let tray = if col == Colour::White { &self.taken_black_pieces } else { &self.taken_white_pieces };
+        let mods = if col == Colour::White { &self.white_mods } else { &self.black_mods };
+        tray.iter().filter(|piece| self.bughouse.is_some() || mods.contains(&Mods::CrazyHouse(**piece)) || self.spare(col, **piece)).copied().collect()
+    }
+
+    /// Whether `piece` in the tray of `col` may be dropped as the spare from a Spare Knight mod.
+    fn spare(&self, col: Colour, piece: PieceType) -> bool {
+        let left = if col == Colour::White { self.spare_knights.0 } else { self.spare_knights.1 };
+        left && piece == PieceType::Knight(piece.colour())
+    }
+
+    /// Uses up the spare knight of `col` once it drops a knight its mods wouldn't otherwise let it drop.
+    fn spend_spare(&mut self, col: Colour, piece: PieceType) {
+        let mods = if col == Colour::White { &self.white_mods } else { &self.black_mods };
+        if self.spare(col, piece) && !mods.contains(&Mods::CrazyHouse(piece)) && self.bughouse.is_none() {
+            if col == Colour::White {
+                self.spare_knights.0 = false;
+            } else {
+                self.spare_knights.1 = false;
+            }
+        }
+    }
+
+    /// Empty squares where the side to move may drop `piece`: never a pawn on the first or last rank,
+    /// and never leaving its own king in check, so while in check only blocking drops remain.
+    fn drop_squares(&self, piece: PieceType) -> Vec<(u8, u8)> {
+        let col = self.board.active_color;
+        let piece = piece.type_as_colour(col);
+        let royal = self.variant != Variant::Antichess && self.variant != Variant::Duck;
+        let mut squares = Vec::new();
+        for file in 1..9 {
+            for rank in 1..9 {
+                if self.board.board.contains_key(&Position { file: file, rank: rank }) || self.duck == Some((file, rank)) {
+                    continue;
+                }
+                if piece == PieceType::Pawn(col) && (rank == 1 || rank == 8) {
+                    continue;
+                }
+                if royal {
+                    let mut theoretical_board = self.board.board.clone();
+                    theoretical_board.insert(Position { file: file, rank: rank }, piece);
+                    let king = theoretical_board.iter().find(|(_, other)| **other == PieceType::King(col)).map(|(pos, _)| (pos.file, pos.rank));
+                    if king.map_or(false, |(file, rank)| square_attacked(&theoretical_board, file, rank, opposite(col)))
+                        || (self.layered() && self.extra_check(&theoretical_board, &self.fairies, col)) {
+                        continue;
+                    }
+                }
+                squares.push((file, rank));
+            }
+        }
+        squares
+    }
+
+    /// Whether the side to move can get out of what the library calls mate by dropping a blocker.
+    fn drop_escapes(&self) -> bool {
+        self.droppable(self.board.active_color).iter().any(|piece| !self.drop_squares(*piece).is_empty())
+    }
+
+    /// Keeps track of promoted pieces after a move from `from` to `to` by `moving`.
+    fn track_promotion(&mut self, from: (u8, u8), to: (u8, u8), moving: PieceType) {
+        let was_promoted = self.promoted.remove(&from);
+        self.promoted.remove(&to);
+        if was_promoted || (moving == PieceType::Pawn(moving.colour()) && (to.1 == 1 || to.1 == 8)) {
+            self.promoted.insert(to);
+        }
+    }
+
+    /// Plays a move directly on the board, bypassing the library's check rules.
+    fn play_plain_move(&mut self, from: (u8, u8), to: (u8, u8)) {
+        let piece = match self.board.board.remove(&Position { file: from.0, rank: from.1 }) {
+            Some(piece) => piece,
+            None => return,
+        };
+        if let Some(taken) = self.board.board.insert(Position { file: to.0, rank: to.1 }, piece) {
+            let taken = if self.promoted.contains(&to) { PieceType::Pawn(taken.colour()) } else { taken };
+            match taken.colour() {
+                Colour::White => self.taken_white_pieces.push(taken),
+                Colour::Black => self.taken_black_pieces.push(taken),
+            }
+        }
+        if piece == PieceType::Pawn(piece.colour()) && (to.1 == 1 || to.1 == 8) {
+            let promotion = self.board.promotion[if piece.colour() == Colour::White { 0 } else { 1 }];
+            self.board.board.insert(Position { file: to.0, rank: to.1 }, promotion.type_as_colour(piece.colour()));
+        }
+        self.update_castling((from.0 as isize, from.1 as isize), (to.0 as isize, to.1 as isize));
+        self.track_promotion(from, to, piece);
+        self.move_layers(from, to);
+        self.board.active_color = opposite(piece.colour());
+    }
+
+    /// Whether either side started from a shuffled back rank, so castling is handled here rather than by the library.
+    fn fischer_random(&self) -> bool {
+        self.castling_files != [(5, 8, 1); 2]
+    }
+
+    /// Whether `col` may castle towards the given side in the current position, using Chess960 rules.
+    fn can_castle(&self, col: Colour, kingside: bool) -> bool {
+        if !self.castling_allowed(col, kingside) {
+            return false;
+        }
+        let rank = if col == Colour::White { 1 } else { 8 };
+        let (king, kingside_rook, queenside_rook) = self.castling_files[if col == Colour::White { 0 } else { 1 }];
+        let rook = if kingside { kingside_rook } else { queenside_rook };
+        let (king_to, rook_to) = if kingside { (7, 6) } else { (3, 4) };
+        if self.board.board.get(&Position { file: king, rank: rank }) != Some(&PieceType::King(col))
+            || self.board.board.get(&Position { file: rook, rank: rank }) != Some(&PieceType::Rook(col)) {
+            return false;
+        }
+
+        // everything the king and rook pass over must be empty, apart from the two of them
+        let files = [king, rook, king_to, rook_to];
+        for file in *files.iter().min().unwrap()..=*files.iter().max().unwrap() {
+            if file != king && file != rook && (self.board.board.contains_key(&Position { file: file, rank: rank }) || self.duck == Some((file, rank))) {
+                return false;
+            }
+        }
+
+        // and the king may not start in, pass through or land in check
+        let mut theoretical_board = self.board.board.clone();
+        theoretical_board.remove(&Position { file: king, rank: rank });
+        theoretical_board.remove(&Position { file: rook, rank: rank });
+        (king.min(king_to)..=king.max(king_to)).all(|file| {
+            !square_attacked(&theoretical_board, file, rank, opposite(col))
+                && !(self.layered() && self.extra_attacked(&theoretical_board, &self.fairies, (file, rank), opposite(col)))
+        })
+    }
+
+    /// Castles `col` towards the given side and passes the turn.
+    fn castle(&mut self, col: Colour, kingside: bool) {
+        let rank = if col == Colour::White { 1 } else { 8 };
+        let (king, kingside_rook, queenside_rook) = self.castling_files[if col == Colour::White { 0 } else { 1 }];
+        let rook = if kingside { kingside_rook } else { queenside_rook };
+        let (king_to, rook_to) = if kingside { (7, 6) } else { (3, 4) };
+        self.board.board.remove(&Position { file: king, rank: rank });
+        self.board.board.remove(&Position { file: rook, rank: rank });
+        self.move_layers((rook, rank), (rook_to, rank));
+        self.board.board.insert(Position { file: king_to, rank: rank }, PieceType::King(col));
+        self.board.board.insert(Position { file: rook_to, rank: rank }, PieceType::Rook(col));
+        if col == Colour::White {
+            self.castling[0] = false;
+            self.castling[1] = false;
+        } else {
+            self.castling[2] = false;
+            self.castling[3] = false;
+        }
+        self.board.get_game_state();
+        self.board.active_color = opposite(col);
+    }
+
+    /// Standard algebraic notation for moving the selected piece or tray piece to `to`, without check markers.
+    fn move_notation(&mut self, from: (isize, isize), to: (isize, isize)) -> String {
+        let target = Position { file: to.0 as u8, rank: to.1 as u8 };
+        if from.1 == 9 || from.1 == 10 {
+            let tray = if from.1 == 9 { &self.taken_black_pieces } else { &self.taken_white_pieces };
+            let piece = tray[from.0 as usize];
+            return format!("{}@{}", piece_char(piece.type_as_colour(Colour::White)), target.to_string());
+        }
+
+        let piece = self.board.board[&Position { file: from.0 as u8, rank: from.1 as u8 }];
+        if piece == PieceType::King(piece.colour()) {
+            let castled_on = self.board.board.get(&target) == Some(&PieceType::Rook(piece.colour()));
+            if castled_on || (to.0 - from.0).abs() == 2 {
+                return if to.0 > from.0 { "O-O".to_string() } else { "O-O-O".to_string() };
+            }
+        }
+
+        let capture = self.board.board.contains_key(&target)
+            || (piece == PieceType::Pawn(piece.colour()) && from.0 != to.0);
+        let mut notation = String::new();
+        if piece == PieceType::Pawn(piece.colour()) {
+            if capture {
+                notation.push((b'a' + from.0 as u8 - 1) as char);
+            }
+        } else {
+            let fairy = self.fairies.get(&(from.0 as u8, from.1 as u8)).copied();
+            notation.push(fairy.map_or(piece_char(piece.type_as_colour(Colour::White)), |fairy| fairy.letter()));
+
+            // name the file or rank when another piece of the same kind could also move there
+            let rivals = self.board.board.iter()
+                .filter(|(pos, other)| **other == piece && (pos.file as isize, pos.rank as isize) != from)
+                .filter(|(pos, _)| self.fairies.get(&(pos.file, pos.rank)).copied() == fairy)
+                .map(|(pos, _)| (pos.file, pos.rank))
+                .collect::<Vec<(u8, u8)>>();
+            let rivals = rivals.into_iter()
+                .filter(|(file, rank)| self.board.get_possible_moves(Position { file: *file, rank: *rank }.to_string()).unwrap_or_default().contains(&target.to_string()))
+                .collect::<Vec<(u8, u8)>>();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|(file, _)| *file as isize != from.0) {
+                    notation.push((b'a' + from.0 as u8 - 1) as char);
+                } else if rivals.iter().all(|(_, rank)| *rank as isize != from.1) {
+                    notation.push_str(&from.1.to_string());
+                } else {
+                    notation.push((b'a' + from.0 as u8 - 1) as char);
+                    notation.push_str(&from.1.to_string());
+                }
+            }
+        }
+        if capture {
+            notation.push('x');
+        }
+        notation.push_str(&target.to_string());
+        if piece == PieceType::Pawn(piece.colour()) && (to.1 == 1 || to.1 == 8) {
+            let promotion = self.board.promotion[if piece.colour() == Colour::White { 0 } else { 1 }];
+            notation.push('=');
+            notation.push(piece_char(promotion.type_as_colour(Colour::White)));
+        }
+        notation
+    }
+
+    /// Adds a played move to the move log, marking check and mate. In the tutorial that finishes the lesson.
+    fn log_move(&mut self, notation: String) {
+        if let Some(tutorial) = self.tutorial.as_mut() {
+            tutorial.done = true;
+        }
+        // pieces blown up or sniped leave no piece behind to have been promoted
+        let board = &self.board.board;
+        self.promoted.retain(|(file, rank)| board.contains_key(&Position { file: *file, rank: *rank }));
+        self.fairies.retain(|(file, rank), fairy| board.get(&Position { file: *file, rank: *rank }).map_or(false, |piece| *piece == fairy.base(piece.colour())));
+        self.shields.retain(|(file, rank)| board.contains_key(&Position { file: *file, rank: *rank }));
+        self.evolving.retain(|(file, rank)| board.contains_key(&Position { file: *file, rank: *rank }));
+        let atomic_check = self.atomic_game() && self.atomic_in_check(&self.board.board, self.board.active_color);
+        let layered_check = self.layered() && self.layered_in_check();
+        let suffix = match self.board.get_game_state() {
+            _ if self.variant == Variant::Antichess || self.variant == Variant::Duck => "",
+            _ if self.atomic_game() && atomic_check && !self.atomic_has_moves() => "#",
+            _ if self.atomic_game() && atomic_check => "+",
+            _ if self.atomic_game() => "",
+            _ if self.layered() && layered_check && !self.layered_has_moves() => "#",
+            _ if self.layered() && layered_check => "+",
+            _ if self.layered() => "",
+            GameState::CheckMate if self.drop_escapes() || self.teleport_escapes() => "+",
+            GameState::CheckMate => "#",
+            GameState::Check => "+",
+            _ => "",
+        };
+        let mut entry = format!("{}{}", notation, suffix);
+        let blast = std::mem::replace(&mut self.blast, Vec::new());
+        if !blast.is_empty() {
+            let squares = blast.iter().map(|(file, rank)| Position { file: *file, rank: *rank }.to_string()).collect::<Vec<String>>();
+            entry.push_str(&format!(" {{blast {}}}", squares.join(" ")));
+        }
+        if let Some((file, rank)) = self.shield_hit.take() {
+            entry.push_str(&format!(" {{shield breaks on {}}}", Position { file: file, rank: rank }.to_string()));
+        }
+        if let Some(piece) = self.evolution.take() {
+            entry.push_str(&format!(" {{evolves to {}}}", piece_char(piece.type_as_colour(Colour::White))));
+        }
+        self.move_log.push(entry);
+    }
+
+    /// Ends a move that has just been logged: the turn passes on, or goes back to the mover if they armed
+    /// a double move, and the position the next player faces is recorded for spotting repetitions.
+    fn end_move(&mut self) {
+        if self.variant != Variant::Duck && self.bughouse.is_none() && self.pass_turn(opposite(self.board.active_color)) {
+            if let Some(entry) = self.move_log.last_mut() {
+                entry.push_str(" {double move}");
+            }
+        }
+        // only now is it settled who moves next, which is part of the position
+        self.position_history.push(self.repetition_key());
+    }
+
+    /// The current game as PGN, with SetUp/FEN tags when it didn't start from the standard position or is a Chess960 game.
+    fn game_pgn(&self) -> String {
+        let result = match (self.screen, self.cur_winner) {
+            (ScreenState::ScoreScreen, Some(Colour::White)) => "1-0",
+            (ScreenState::ScoreScreen, Some(Colour::Black)) => "0-1",
+            (ScreenState::ScoreScreen, None) => "1/2-1/2",
+            _ => "*",
+        };
+        let mut pgn = String::new();
+        pgn.push_str("[Event \"Modded chess series\"]\n");
+        pgn.push_str(&format!("[Round \"{}\"]\n", self.wins.0 as u16 + self.wins.1 as u16 + 1));
+        pgn.push_str(&format!("[White \"{}\"]\n", self.players[0]));
+        pgn.push_str(&format!("[Black \"{}\"]\n", self.players[1]));
+        pgn.push_str(&format!("[Result \"{}\"]\n", result));
+        // the Chess960 draw can land on the standard setup, which is still a Chess960 game
+        let chess960 = self.white_mods.contains(&Mods::Chess960) || self.black_mods.contains(&Mods::Chess960);
+        if self.variant == Variant::Antichess {
+            pgn.push_str("[Variant \"Antichess\"]\n");
+        } else if self.variant == Variant::RacingKings {
+            pgn.push_str("[Variant \"Racing Kings\"]\n");
+        } else if self.variant == Variant::Duck {
+            pgn.push_str("[Variant \"Duck\"]\n");
+        } else if chess960 {
+            pgn.push_str("[Variant \"Chess960\"]\n");
+        }
+        if self.start_fen != STANDARD_FEN || chess960 {
+            pgn.push_str("[SetUp \"1\"]\n");
+            pgn.push_str(&format!("[FEN \"{}\"]\n", self.start_fen));
+        }
+        pgn.push('\n');
+
+        // a game set up with Black to move starts its numbering with "1...", and the reply
+        // skipped by a double move is written as the null move "--" to keep the numbering right
+        let black_first = self.start_fen.split(' ').nth(1) == Some("b");
+        let mut movetext = Vec::new();
+        let mut ply = if black_first { 1 } else { 0 };
+        for notation in self.move_log.iter() {
+            let skipped = notation.ends_with("{double move}");
+            for mov in [notation.as_str(), "--"].iter().take(if skipped { 2 } else { 1 }) {
+                if ply % 2 == 0 {
+                    movetext.push(format!("{}.", ply / 2 + 1));
+                } else if movetext.is_empty() {
+                    movetext.push(format!("{}...", ply / 2 + 1));
+                }
+                movetext.push(mov.to_string());
+                ply += 1;
+            }
+        }
+        movetext.push(result.to_string());
+        pgn.push_str(&movetext.join(" "));
+        pgn.push('\n');
+        pgn
+    }
+
+    /// Whether `col` may still castle towards the given side.
+    fn castling_allowed(&self, col: Colour, kingside: bool) -> bool {
+        match (col, kingside) {
+            (Colour::White, true) => self.castling[0],
+            (Colour::White, false) => self.castling[1],
+            (Colour::Black, true) => self.castling[2],
+            (Colour::Black, false) => self.castling[3],
+        }
+    }
+
+    /// Revokes castling rights when a king or rook leaves, or a rook is captured on, its home square.
+    fn update_castling(&mut self, from: (isize, isize), to: (isize, isize)) {
+        for pos in [from, to].iter() {
+            for (i, rank) in [1, 8].iter().enumerate() {
+                let (king, kingside_rook, queenside_rook) = self.castling_files[i];
+                if pos.1 != *rank {
+                    continue;
+                }
+                if pos.0 == king as isize || pos.0 == kingside_rook as isize {
+                    self.castling[i * 2] = false;
+                }
+                if pos.0 == king as isize || pos.0 == queenside_rook as isize {
+                    self.castling[i * 2 + 1] = false;
+                }
+            }
+        }
+    }
+
+    /// Checks that the edited position can be played, returning the reason if not.
+    fn validate_position(&self) -> Result<(), String> {
+        for col in [Colour::White, Colour::Black].iter() {
+            let kings = self.board.board.values().filter(|piece| **piece == PieceType::King(*col)).count();
+            if kings != 1 {
+                return Err(format!("{:?} needs exactly one king, not {}.", col, kings));
+            }
+        }
+        if self.board.board.iter().any(|(pos, piece)| (pos.rank == 1 || pos.rank == 8) && piece == &PieceType::Pawn(piece.colour())) {
+            return Err("Pawns can't stand on the first or last rank.".to_string());
+        }
+        for (i, (col, rank)) in [(Colour::White, 1), (Colour::Black, 8)].iter().enumerate() {
+            let (king, kingside_rook, queenside_rook) = self.castling_files[i];
+            let on = |file: u8, piece: PieceType| self.board.board.get(&Position { file: file, rank: *rank }) == Some(&piece);
+            for (right, rook, side) in [(i * 2, kingside_rook, "kingside"), (i * 2 + 1, queenside_rook, "queenside")].iter() {
+                if self.castling[*right] && !(on(king, PieceType::King(*col)) && on(*rook, PieceType::Rook(*col))) {
+                    return Err(format!("{:?} can't castle {} unless its king and rook are on their squares.", col, side));
+                }
+            }
+        }
+        let waiting = opposite(self.board.active_color);
+        if self.king_attacked(waiting) {
+            return Err(format!("{:?} is in check, but it's {:?}'s move.", waiting, self.board.active_color));
+        }
+        Ok(())
+    }
+
+    /// FEN of the current position, with any captured pieces as crazyhouse-style pockets.
+    fn position_fen(&self) -> String {
+        let mut placement = fen_placement(&self.board.board, &self.fairies);
+
+        // white drops the black pieces it took and vice versa
+        let pockets = self.taken_black_pieces.iter().map(|piece| piece_char(piece.type_as_colour(Colour::White)))
+            .chain(self.taken_white_pieces.iter().map(|piece| piece_char(piece.type_as_colour(Colour::Black))))
+            .collect::<String>();
+
+        // shuffled back ranks name the castling rooks by file, as in Shredder-FEN
+        let labels = if self.fischer_random() {
+            let files = self.castling_files;
+            [(b'A' + files[0].1 - 1) as char, (b'A' + files[0].2 - 1) as char, (b'a' + files[1].1 - 1) as char, (b'a' + files[1].2 - 1) as char]
+        } else {
+            ['K', 'Q', 'k', 'q']
+        };
+        let mut castling = labels.iter().zip(self.castling.iter())
+            .filter(|(_, allowed)| **allowed)
+            .map(|(c, _)| *c)
+            .collect::<String>();
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        if !pockets.is_empty() {
+            placement = format!("{}[{}]", placement, pockets);
+        }
+
+        format!("{} {} {} - 0 1", placement, if self.board.active_color == Colour::White { "w" } else { "b" }, castling)
+    }
+
+    /// The position as counted for repetition: its FEN plus the shields still up, which change what can be taken.
+    fn repetition_key(&self) -> String {
+        let mut shields = self.shields.iter().map(|(file, rank)| Position { file: *file, rank: *rank }.to_string()).collect::<Vec<String>>();
+        if shields.is_empty() {
+            return self.position_fen();
+        }
+        shields.sort();
+        format!("{} {{shields {}}}", self.position_fen(), shields.join(" "))
+    }
+
+    /// Handles a click on the board editor.
+    fn editor_click(&mut self, button: MouseButton, x: f32, y: f32) {
+        let point = ggez::mint::Point2 { x: x, y: y };
+        self.editor_message = String::new();
+
+        // place and remove pieces on the board
+        if let Some((file, rank)) = board_square_at(x, y) {
+            let pos = Position { file: file, rank: rank };
+            match (button, self.editor_piece) {
+                (MouseButton::Left, Some(piece)) => { self.board.board.insert(pos, piece); },
+                _ => { self.board.board.remove(&pos); },
+            }
+            // whatever the old piece carried goes with it
+            self.clear_layers((file, rank));
+            self.promoted.remove(&(file, rank));
+            if self.portals.map_or(false, |(a, b)| a == (file, rank) || b == (file, rank)) {
+                self.portals = None;
+            }
+            return;
+        }
+
+        // and in the trays
+        if x >= SCREEN_SIZE.0 * 0.25 - GRID_CELL_SIZE.0 as f32 && y >= (GRID_CELL_SIZE.1 * 9) as f32 && y < (GRID_CELL_SIZE.1 * 11) as f32 {
+            let slot = ((x - (SCREEN_SIZE.0 * 0.25 - GRID_CELL_SIZE.0 as f32)) / GRID_CELL_SIZE.0 as f32).floor() as usize;
+            let (tray, col) = if y < (GRID_CELL_SIZE.1 * 10) as f32 {
+                (&mut self.taken_black_pieces, Colour::Black)
+            } else {
+                (&mut self.taken_white_pieces, Colour::White)
+            };
+            match (button, self.editor_piece) {
+                (MouseButton::Left, Some(piece)) => tray.push(piece.type_as_colour(col)),
+                _ => if slot < tray.len() { tray.remove(slot); },
+            }
+            return;
+        }
+
+        // clicking a held mod takes it away
+        if let Some((col, modi)) = self.mod_row_at(x, y) {
+            if col == Colour::White { self.white_mods.remove(&modi); } else { self.black_mods.remove(&modi); }
+            return;
+        }
+
+        if button != MouseButton::Left {
+            return;
+        }
+
+        for (i, piece) in editor_palette().iter().enumerate() {
+            if panel_rect((i % 6) as f32, (i / 6) as f32, 1.0).contains(point) {
+                self.editor_piece = Some(*piece);
+                return;
+            }
+        }
+        if panel_rect(0.0, 2.0, 6.0).contains(point) {
+            self.editor_piece = None;
+        } else if panel_rect(0.0, 3.0, 6.0).contains(point) {
+            self.board.active_color = opposite(self.board.active_color);
+        } else if panel_rect(0.0, 5.0, 1.0).contains(point) {
+            self.editor_mod = (self.editor_mod + all_mods(Colour::White).len() - 1) % all_mods(Colour::White).len();
+        } else if panel_rect(5.0, 5.0, 1.0).contains(point) {
+            self.editor_mod = (self.editor_mod + 1) % all_mods(Colour::White).len();
+        } else if panel_rect(0.0, 6.0, 3.0).contains(point) {
+            self.white_mods.insert(all_mods(Colour::White)[self.editor_mod]);
+        } else if panel_rect(3.0, 6.0, 3.0).contains(point) {
+            self.black_mods.insert(all_mods(Colour::Black)[self.editor_mod]);
+        } else if panel_rect(0.0, 7.0, 3.0).contains(point) {
+            self.board.board.clear();
+            self.taken_black_pieces = Vec::new();
+            self.taken_white_pieces = Vec::new();
+            self.castling = [false; 4];
+            self.clear_editor_layers();
+        } else if panel_rect(3.0, 7.0, 3.0).contains(point) {
+            self.board = Game::new();
+            self.castling = [true; 4];
+            self.castling_files = [(5, 8, 1); 2];
+            self.clear_editor_layers();
+        } else if panel_rect(0.0, 8.0, 3.0).contains(point) {
+            self.leave_editor();
+        } else if panel_rect(3.0, 8.0, 3.0).contains(point) {
+            match self.validate_position() {
+                Ok(()) => {
+                    let fen = self.position_fen();
+                    self.editor_message = match std::fs::write(POSITION_FILE, format!("{}\n", fen)) {
+                        Ok(()) => format!("Saved to {}", POSITION_FILE),
+                        Err(e) => {
+                            println!("Couldn't save {}: {}", POSITION_FILE, e);
+                            format!("Couldn't save {}: {}", POSITION_FILE, e)
+                        },
+                    };
+                },
+                Err(e) => self.editor_message = e,
+            }
+        } else {
+            for i in 0..4 {
+                if panel_rect(i as f32, 4.0, 1.0).contains(point) {
+                    self.castling[i] = !self.castling[i];
+                }
+            }
+        }
+    }
+
+    /// Drops the fairy, shield, portal, evolution and promotion layers from every square of the edited board.
+    fn clear_editor_layers(&mut self) {
+        self.fairies = HashMap::new();
+        self.shields = HashSet::new();
+        self.evolving = HashSet::new();
+        self.promoted = HashSet::new();
+        self.portals = None;
+    }
+
+    /// Starts play from the edited position if it is valid.
+    fn leave_editor(&mut self) {
+        match self.validate_position() {
+            Ok(()) => {
+                self.screen = ScreenState::GameScreen;
+                // the mods may have changed too, so lay out their squares afresh as a new game would
+                self.clear_editor_layers();
+                self.place_portals();
+                self.place_fairies();
+                self.place_shields();
+                self.editor_backup = None;
+                self.variant_backup = None;
+                self.undo_stack = Vec::new();
+                self.game_mods = self.held_mod_names();
+                self.start_fen = self.position_fen();
+                self.move_log = Vec::new();
+                self.position_history = Vec::new();
+                self.triple_check_counter = (0, 0);
+                self.duck = None;
+                self.duck_pending = false;
+                self.selected_pos = (0, 0);
+                self.highlighted_pos = Vec::new();
+            },
+            Err(e) => self.editor_message = e,
+        }
+    }
+
+    /// Rolls a single mod for the loser that they neither hold nor have banished.
+    fn roll_mod_offer(&self) -> Option<Mods> {
+        self.roll_mod_for(opposite(self.cur_winner.unwrap()))
+    }
+
+    /// Rolls a mod for `col` that they neither hold, have banished nor already have on offer, and that doesn't conflict
+    /// with any mod in play. Gives up with None once `OFFER_TRIES` rolls have all been ruled out.
+    fn roll_mod_for(&self, col: Colour) -> Option<Mods> {
+        let (held, banished) = match col {
+            Colour::White => (&self.white_mods, &self.white_banished),
+            Colour::Black => (&self.black_mods, &self.black_banished),
+        };
+        unsafe {
+            let mut rng: rngs::StdRng = rand::SeedableRng::seed_from_u64(seed);
+            seed += 1;
+            (0..OFFER_TRIES)
+                .map(|_| generate_mod(col, rng.gen(), rng.gen()))
+                .find(|offer| !held.contains(offer) && !banished.contains(offer) && !self.random_mods.contains(&Some(*offer)) && !self.white_mods.iter().chain(self.black_mods.iter()).any(|modi| conflicts(modi, offer)))
+        }
+    }
+
+    /// Finds the held mod listed in the mod panel under the given screen coordinates.
+    fn mod_row_at(&self, x: f32, y: f32) -> Option<(Colour, Mods)> {
+        if x < 0.0 || x >= SCREEN_SIZE.0 * 0.25 - GRID_CELL_SIZE.0 as f32 {
+            return None;
+        }
+        let (col, mods, top) = if y < SCREEN_SIZE.1 * 0.5 {
+            (Colour::White, &self.white_mods, 0f32)
+        } else {
+            (Colour::Black, &self.black_mods, SCREEN_SIZE.1 * 0.5)
+        };
+        // rows start one line below the panel title
+        let row = ((y - top) / 25f32).floor() as isize - 1;
+        if row < 0 {
+            return None;
+        }
+        mods.iter().nth(row as usize).map(|modi| (col, *modi))
+    }
+
+    /// Mod under the mouse cursor, either in a mod panel or among the offers.
+    fn hovered_mod(&self) -> Option<Mods> {
+        let (x, y) = self.mouse_pos;
+        match self.screen {
+            ScreenState::GameScreen | ScreenState::ScoreScreen | ScreenState::EditorScreen => self.mod_row_at(x, y).map(|(_, modi)| modi),
+            ScreenState::ModScreen => {
+                let point = ggez::mint::Point2 { x: x, y: y };
+                (0..self.random_mods.len())
+                    .find(|i| mod_offer_rect(*i).contains(point) && !mod_banish_rect(*i).contains(point))
+                    .and_then(|i| self.random_mods[i])
+            },
+            ScreenState::HelpScreen | ScreenState::StatsScreen | ScreenState::ProfileScreen => None,
+        }
+    }
+
+    /// Lets the winner spend their win to remove one of the loser's mods.
+    fn strip_mod(&mut self, col: Colour, modi: Mods) {
+        if self.win_spent || self.cur_winner != Some(opposite(col)) {
+            return;
+        }
+        match col {
+            Colour::White => {
+                self.white_mods.remove(&modi);
+                self.wins.1 -= 1;
+            },
+            Colour::Black => {
+                self.black_mods.remove(&modi);
+                self.wins.0 -= 1;
+            },
+        }
+        self.win_spent = true;
+    }
+
+    /// Trades one of a player's held mods for a fresh roll, once per score screen.
+    fn swap_mod(&mut self, col: Colour, modi: Mods) {
+        let already_swapped = if col == Colour::White { self.swapped.0 } else { self.swapped.1 };
+        if already_swapped {
+            return;
+        }
+        let fresh = match self.roll_mod_for(col) {
+            Some(fresh) => fresh,
+            None => return,
+        };
+        match col {
+            Colour::White => {
+                self.white_mods.remove(&modi);
+                self.white_mods.insert(fresh);
+                self.swapped.0 = true;
+            },
+            Colour::Black => {
+                self.black_mods.remove(&modi);
+                self.black_mods.insert(fresh);
+                self.swapped.1 = true;
+            },
+        }
+    }
+
+    /// Replaces all three offers on the mod screen with fresh rolls.
+    fn roll_mod_offers(&mut self) {
+        self.random_mods = Vec::new();
+        for _ in 0..3 {
+            let offer = self.roll_mod_offer();
+            self.random_mods.push(offer);
+        }
+    }
+}
+
+/// Implement each stage of the application event loop. 
+impl event::EventHandler for AppState {
+
+    /// For updating game logic, which front-end doesn't handle.
+    fn update(&mut self, _ctx: &mut Context) -> GameResult {
+        self.step(timer::delta(_ctx).as_secs_f32());
+        Ok(())
+    }
+
+    /// Draw interface, i.e. draw game board
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        // clear interface with gray background colour
+        graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
+
+        if self.screen == ScreenState::GameScreen || self.screen == ScreenState::ScoreScreen || self.screen == ScreenState::EditorScreen {
+            // create text representation
+            let state_text = graphics::Text::new(
+                    graphics::TextFragment::from(match self.variant {
+                        Variant::Antichess => format!("Antichess game."),
+                        Variant::RacingKings => format!("Racing Kings game."),
+                        Variant::Duck if self.duck_pending => format!("Duck chess: place the duck."),
+                        Variant::Duck => format!("Duck chess game."),
+                        Variant::Standard => format!("Game is {:?}.", self.board.get_game_state()),
+                    }
+                )
+                .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+            let turn_text = graphics::Text::new(
+                    graphics::TextFragment::from(format!("Current turn is {:?}.", self.board.active_color)
+                )
+                .scale(graphics::Scale { x: 18.5, y: 20.0 })); //dont ask
+            let promotion_text = graphics::Text::new(
+                    graphics::TextFragment::from(format!("Current promotion:")
+                )
+                .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+            let mut help_text = graphics::Text::new(
+                    graphics::TextFragment::from(format!("H: mod rules, S: statistics, E: edit board, U: undo, Ctrl+S/Ctrl+L: save/load game, P: save PGN, A/R/D: antichess/racing kings/duck series ({:?}), B: bughouse, C: alternate colours ({}).", self.series_variant, if self.alternate { "on" } else { "off" })
+                )
+                .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+            help_text.set_bounds(ggez::mint::Point2 { x: SCREEN_SIZE.0 * 0.25 - 10f32, y: f32::INFINITY }, graphics::Align::Left);
+            let black_mod_title = graphics::Text::new(
+                graphics::TextFragment::from(format!("{} mods:", self.side_label(Colour::Black))
+            )
+                .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+            let white_mod_title = graphics::Text::new(
+                graphics::TextFragment::from(format!("{} mods:", self.side_label(Colour::White))
+            )
+                .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+
+            // get size of text
+            let text_dimensions = state_text.dimensions(ctx);
+            // create background rectangle with white coulouring
+            let background_box = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(),
+                graphics::Rect::new(0f32,
+                                    0f32,
+                                    SCREEN_SIZE.0, SCREEN_SIZE.1),
+                                    [1.0, 1.0, 1.0, 1.0].into()
+            )?;
+
+            // draw background
+            graphics::draw(ctx, &background_box, DrawParam::default());
+
+            // draw tiles
+            for i in 0..64 {
+                let rectangle = graphics::Mesh::new_rectangle(ctx, 
+                    graphics::DrawMode::fill(), 
+                    graphics::Rect::new_i32(
+                        (i % 8 * GRID_CELL_SIZE.0 as i32) + (SCREEN_SIZE.0 as i32 / 4),
+                        i / 8 * GRID_CELL_SIZE.1 as i32,
+                        GRID_CELL_SIZE.0 as i32,
+                        GRID_CELL_SIZE.1 as i32,
+                    ), if int_to_pos_tuple(i as isize) == self.selected_pos || self.highlighted_pos.contains(&int_to_pos_tuple(i as isize)) { if (int_to_pos_tuple(i as isize).0 % 2 == 0) ^ (int_to_pos_tuple(i as isize).1 % 2 == 0) { BLACK_RED } else { WHITE_RED } }
+                    else { match i % 2 {
+                        0 => match i / 8 {
+                            _row if _row % 2 == 0 => WHITE,
+                            _ => BLACK
+                        },
+                        _ => match i / 8 {
+                            _row if _row % 2 == 0 => BLACK,
+                            _ => WHITE
+                        }
+                    }})?;
+                graphics::draw(ctx, &rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+            }
+
+            // draw portals
+            if let Some((portal_a, portal_b)) = self.portals {
+                for (file, rank) in [portal_a, portal_b].iter() {
+                    let centre = ggez::mint::Point2 {
+                        x: (*file as f32 - 0.5) * GRID_CELL_SIZE.0 as f32 + SCREEN_SIZE.0 * 0.25,
+                        y: (8.5 - *rank as f32) * GRID_CELL_SIZE.1 as f32,
+                    };
+                    let portal = graphics::Mesh::new_circle(ctx, DrawMode::stroke(4.0), centre, GRID_CELL_SIZE.0 as f32 * 0.4, 0.5, Color::new(0.45, 0.2, 0.85, 1.0))?;
+                    graphics::draw(ctx, &portal, DrawParam::default());
+                }
+            }
+
+            // draw selected taken piece
+            if self.selected_pos.1 == 9 || self.selected_pos.1 == 10 {
+                let rectangle = graphics::Mesh::new_rectangle(ctx, 
+                    graphics::DrawMode::fill(), 
+                    graphics::Rect::new_i32(
+                        (self.selected_pos.0 * GRID_CELL_SIZE.0 as isize) as i32 + ((SCREEN_SIZE.0 * 0.25) - GRID_CELL_SIZE.0 as f32) as i32,
+                        self.selected_pos.1 as i32 * GRID_CELL_SIZE.1 as i32,
+                        GRID_CELL_SIZE.0 as i32,
+                        GRID_CELL_SIZE.1 as i32,
+                    ), BLACK_RED)?;
+                graphics::draw(ctx, &rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+            }
+
+            // draw pieces
+            for (pos, val) in self.board.board.iter() {
+                let sprite = match self.fairies.get(&(pos.file, pos.rank)) {
+                    Some(fairy) => &self.fairy_sprites[fairy][if val.colour() == Colour::White { 0 } else { 1 }],
+                    None => &self.sprites[val],
+                };
+                graphics::draw(ctx, sprite, DrawParam::default().color(self.piece_tint(val.colour()))
+                    .dest(ggez::mint::Point2 { x: ((pos.file - 1) as f32 * GRID_CELL_SIZE.0 as f32) + SCREEN_SIZE.0 * 0.25 as f32, y: (8 - pos.rank) as f32 * GRID_CELL_SIZE.1 as f32 }));
+            }
+            for (file, rank) in self.shields.iter() {
+                let centre = ggez::mint::Point2 {
+                    x: (*file as f32 - 0.5) * GRID_CELL_SIZE.0 as f32 + SCREEN_SIZE.0 * 0.25,
+                    y: (8.5 - *rank as f32) * GRID_CELL_SIZE.1 as f32,
+                };
+                let shield = graphics::Mesh::new_circle(ctx, DrawMode::stroke(3.0), centre, GRID_CELL_SIZE.0 as f32 * 0.45, 0.5, Color::new(0.3, 0.75, 1.0, 0.9))?;
+                graphics::draw(ctx, &shield, DrawParam::default());
+            }
+            for ((file, rank), left) in self.explosions.iter() {
+                // a flash over the blast area that grows and fades
+                let progress = 1.0 - left / EXPLOSION_TIME;
+                let size = GRID_CELL_SIZE.0 as f32 * (1.0 + 2.0 * progress);
+                let centre = ((*file as f32 - 0.5) * GRID_CELL_SIZE.0 as f32 + SCREEN_SIZE.0 * 0.25, (8.5 - *rank as f32) * GRID_CELL_SIZE.1 as f32);
+                let flash = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(),
+                    graphics::Rect::new(centre.0 - size * 0.5, centre.1 - size * 0.5, size, size),
+                    Color::new(1.0, 0.55, 0.1, 0.8 * (1.0 - progress)))?;
+                graphics::draw(ctx, &flash, DrawParam::default());
+            }
+            if let (Some((file, rank)), Some(duck_sprite)) = (self.duck, &self.duck_sprite) {
+                graphics::draw(ctx, duck_sprite, (ggez::mint::Point2 { x: ((file - 1) as f32 * GRID_CELL_SIZE.0 as f32) + SCREEN_SIZE.0 * 0.25 as f32, y: (8 - rank) as f32 * GRID_CELL_SIZE.1 as f32 }, ));
+            }
+
+            // draw taken pieces
+            for x in 0..self.taken_black_pieces.len() {
+                graphics::draw(ctx, &self.sprites[&self.taken_black_pieces[x]], DrawParam::default().color(self.piece_tint(self.taken_black_pieces[x].colour()))
+                    .dest(ggez::mint::Point2 { x: SCREEN_SIZE.0 * 0.25 - GRID_CELL_SIZE.0 as f32 + (GRID_CELL_SIZE.0 as usize * x) as f32, y: 9f32 * GRID_CELL_SIZE.1 as f32 }));
+            }
+            for x in 0..self.taken_white_pieces.len() {
+                graphics::draw(ctx, &self.sprites[&self.taken_white_pieces[x]], DrawParam::default().color(self.piece_tint(self.taken_white_pieces[x].colour()))
+                    .dest(ggez::mint::Point2 { x: SCREEN_SIZE.0 * 0.25 - GRID_CELL_SIZE.0 as f32 + (GRID_CELL_SIZE.0 as usize * x) as f32, y: 10f32 * GRID_CELL_SIZE.1 as f32 }));
+            }
+
+            // draw the partner board, its trays and both boards' clocks
+            if let Some(bughouse) = &self.bughouse {
+                let origin = partner_board_rect();
+                for i in 0..64 {
+                    let tile = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(),
+                        graphics::Rect::new(origin.x + (i % 8) as f32 * MINI_CELL_SIZE, origin.y + (i / 8) as f32 * MINI_CELL_SIZE, MINI_CELL_SIZE, MINI_CELL_SIZE),
+                        if (i % 8 + i / 8) % 2 == 0 { WHITE } else { BLACK })?;
+                    graphics::draw(ctx, &tile, DrawParam::default());
+                }
+                for (pos, val) in bughouse.board.board.iter() {
+                    graphics::draw(ctx, &self.sprites[val], DrawParam::default()
+                        .dest(ggez::mint::Point2 { x: origin.x + (pos.file - 1) as f32 * MINI_CELL_SIZE, y: origin.y + (8 - pos.rank) as f32 * MINI_CELL_SIZE })
+                        .scale(ggez::mint::Vector2 { x: 0.5, y: 0.5 }));
+                }
+                for (row, tray) in [&bughouse.taken_black_pieces, &bughouse.taken_white_pieces].iter().enumerate() {
+                    for (i, piece) in tray.iter().enumerate() {
+                        graphics::draw(ctx, &self.sprites[piece], DrawParam::default()
+                            .dest(ggez::mint::Point2 { x: origin.x + (i % 8) as f32 * MINI_CELL_SIZE, y: origin.y + origin.h + ((row * 2 + i / 8) as f32 + 0.25) * MINI_CELL_SIZE })
+                            .scale(ggez::mint::Vector2 { x: 0.5, y: 0.5 }));
+                    }
+                }
+                let names = if bughouse.swapped { ["B", "A"] } else { ["A", "B"] };
+                let mut clock_text = graphics::Text::new(graphics::TextFragment::from(format!(
+                    "Board {}: White {} Black {}\nBoard {}: White {} Black {}\n{}",
+                    names[0], clock_string(bughouse.clocks[0].0), clock_string(bughouse.clocks[0].1),
+                    names[1], clock_string(bughouse.clocks[1].0), clock_string(bughouse.clocks[1].1),
+                    bughouse.link.as_ref().map_or("", |link| link.status.as_str()),
+                )).scale(graphics::Scale { x: 16.0, y: 16.0 }));
+                clock_text.set_bounds(ggez::mint::Point2 { x: origin.w, y: f32::INFINITY }, graphics::Align::Left);
+                graphics::draw(ctx, &clock_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 { x: origin.x, y: origin.y + origin.h + MINI_CELL_SIZE * 4.5 }));
+            }
+
+            if self.screen != ScreenState::EditorScreen {
+                // draw promotion selectors
+                let current_color = self.board.active_color;
+                let turn_idx = if current_color == Colour::White { 0 } else { 1 };
+                let promo_y = match self.board.promotion[turn_idx] {
+                    PieceType::Queen(current_color) => (SCREEN_SIZE.0 * 0.75) as i32,
+                    PieceType::Rook(current_color) => (SCREEN_SIZE.0 * 0.75 + GRID_CELL_SIZE.0 as f32) as i32,
+                    PieceType::Bishop(current_color) => (SCREEN_SIZE.0 * 0.75 + GRID_CELL_SIZE.0 as f32 * 2f32) as i32,
+                    PieceType::Knight(current_color) => (SCREEN_SIZE.0 * 0.75 + GRID_CELL_SIZE.0 as f32 * 3f32) as i32,
+                    _ => panic!(),
+                };
+                let rectangle = graphics::Mesh::new_rectangle(ctx, 
+                    graphics::DrawMode::fill(), 
+                    graphics::Rect::new_i32(
+                        promo_y,
+                        (GRID_CELL_SIZE.1 * 3) as i32,
+                        GRID_CELL_SIZE.0 as i32,
+                        GRID_CELL_SIZE.1 as i32,
+                    ), BLACK_RED)?;
+                graphics::draw(ctx, &rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+
+                graphics::draw(ctx, &self.sprites[&PieceType::Queen(self.board.active_color)], (ggez::mint::Point2 { x: SCREEN_SIZE.0 * 0.75, y: 3f32 * GRID_CELL_SIZE.1 as f32 }, ));
+                graphics::draw(ctx, &self.sprites[&PieceType::Rook(self.board.active_color)], (ggez::mint::Point2 { x: SCREEN_SIZE.0 * 0.75 + GRID_CELL_SIZE.0 as f32, y: 3f32 * GRID_CELL_SIZE.1 as f32 }, ));
+                graphics::draw(ctx, &self.sprites[&PieceType::Bishop(self.board.active_color)], (ggez::mint::Point2 { x: SCREEN_SIZE.0 * 0.75 + GRID_CELL_SIZE.0 as f32 * 2f32, y: 3f32 * GRID_CELL_SIZE.1 as f32 }, ));
+                graphics::draw(ctx, &self.sprites[&PieceType::Knight(self.board.active_color)], (ggez::mint::Point2 { x: SCREEN_SIZE.0 * 0.75 + GRID_CELL_SIZE.0 as f32 * 3f32, y: 3f32 * GRID_CELL_SIZE.1 as f32 }, ));
+            
+                // draw text with dark gray colouring and center position
+                graphics::draw(ctx, &state_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: SCREEN_SIZE.0 * 0.75,
+                        y: 0f32,
+                    }));
+                graphics::draw(ctx, &turn_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: SCREEN_SIZE.0 * 0.75,
+                        y: GRID_CELL_SIZE.1 as f32 * 1f32,
+                    }));
+                graphics::draw(ctx, &promotion_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: SCREEN_SIZE.0 * 0.75,
+                        y: GRID_CELL_SIZE.1 as f32 * 2f32,
+                    }));
+                graphics::draw(ctx, &help_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: SCREEN_SIZE.0 * 0.75,
+                        y: GRID_CELL_SIZE.1 as f32 * 5f32,
+                    }));
+            }
+            graphics::draw(ctx, &white_mod_title, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: 0f32,
+                    y: 0f32,
+                }));
+            {
+                let mut x = 0.0;
+            for modi in self.white_mods.iter() {
+                let text = graphics::Text::new(
+                    graphics::TextFragment::from(self.panel_label(Colour::White, modi)
+                )
+                    .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+                    x += 1.0;
+                graphics::draw(ctx, &text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: 0f32,
+                    y: 25f32 * x,
+                }));
+                } 
+            }
+            graphics::draw(ctx, &black_mod_title, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: 0f32,
+                    y: SCREEN_SIZE.1 * 0.5,
+                }));
+                {
+                    let mut x = 0.0;
+                for modi in self.black_mods.iter() {
+                    let text = graphics::Text::new(
+                        graphics::TextFragment::from(self.panel_label(Colour::Black, modi)
+                    )
+                        .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+                        x += 1.0;
+                    graphics::draw(ctx, &text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: 0f32,
+                        y: SCREEN_SIZE.1 * 0.5 + 25f32 * x,
+                    }));
+                    } 
+                }
+            
+            
+            // draw tutorial instructions
+            if let Some(tutorial) = &self.tutorial {
+                let lesson = &lessons()[tutorial.lesson];
+                let message = if tutorial.done {
+                    format!("{}\n\nPress N for the next lesson or Escape to leave.", lesson.outcome)
+                } else {
+                    format!("Lesson {}/{}: {}\n\n{}", tutorial.lesson + 1, lessons().len(), lesson.modi.display_name(Colour::White), lesson.prompt)
+                };
+                let mut text = graphics::Text::new(
+                    graphics::TextFragment::from(message
+                )
+                .scale(graphics::Scale { x: 18.0, y: 18.0 }));
+                text.set_bounds(ggez::mint::Point2 { x: SCREEN_SIZE.0 * 0.25 - 10f32, y: f32::INFINITY }, graphics::Align::Left);
+                graphics::draw(ctx, &text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: SCREEN_SIZE.0 * 0.75,
+                        y: GRID_CELL_SIZE.1 as f32 * 7f32,
+                    }));
+
+                if !tutorial.done {
+                    for pos in [lesson.from, lesson.to].iter() {
+                        let outline = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(3f32), square_rect(*pos), [0.0, 0.0, 0.0, 1.0].into())?;
+                        graphics::draw(ctx, &outline, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+                    }
+                }
+            }
+
+            // draw the editor tools in place of the game information
+            if self.screen == ScreenState::EditorScreen {
+                for (i, piece) in editor_palette().iter().enumerate() {
+                    let slot = panel_rect((i % 6) as f32, (i / 6) as f32, 1.0);
+                    if self.editor_piece == Some(*piece) {
+                        let selected = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), slot, BLACK_RED)?;
+                        graphics::draw(ctx, &selected, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+                    }
+                    graphics::draw(ctx, &self.sprites[piece], (ggez::mint::Point2 { x: slot.x, y: slot.y }, ));
+                }
+
+                let castling_labels = ["K", "Q", "k", "q"];
+                let mut buttons = vec![
+                    (panel_rect(0.0, 2.0, 6.0), self.editor_piece == None, format!("Eraser")),
+                    (panel_rect(0.0, 3.0, 6.0), false, format!("To move: {:?}", self.board.active_color)),
+                    (panel_rect(0.0, 5.0, 1.0), false, format!("<")),
+                    (panel_rect(1.0, 5.0, 4.0), false, all_mods(Colour::White)[self.editor_mod].display_name(Colour::White)),
+                    (panel_rect(5.0, 5.0, 1.0), false, format!(">")),
+                    (panel_rect(0.0, 6.0, 3.0), false, format!("Give White")),
+                    (panel_rect(3.0, 6.0, 3.0), false, format!("Give Black")),
+                    (panel_rect(0.0, 7.0, 3.0), false, format!("Clear")),
+                    (panel_rect(3.0, 7.0, 3.0), false, format!("Reset")),
+                    (panel_rect(0.0, 8.0, 3.0), false, format!("Play")),
+                    (panel_rect(3.0, 8.0, 3.0), false, format!("Export")),
+                ];
+                for i in 0..4 {
+                    buttons.push((panel_rect(i as f32, 4.0, 1.0), self.castling[i], castling_labels[i].to_string()));
+                }
+                for (rect, active, label) in buttons.iter() {
+                    let fill = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), *rect, if *active { BLACK_RED } else { BLACK })?;
+                    let border = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(1f32), *rect, [0.0, 0.0, 0.0, 1.0].into())?;
+                    graphics::draw(ctx, &fill, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+                    graphics::draw(ctx, &border, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+                    let text = graphics::Text::new(
+                        graphics::TextFragment::from(label.clone()
+                    )
+                    .scale(graphics::Scale { x: 18.0, y: 18.0 }));
+                    graphics::draw(ctx, &text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                        .dest(ggez::mint::Point2 {
+                            x: rect.x + 5f32,
+                            y: rect.y + GRID_CELL_SIZE.1 as f32 * 0.25,
+                        }));
+                }
+
+                let mut message = graphics::Text::new(
+                    graphics::TextFragment::from(format!("Left-click to place, right-click to remove. Press E to play or Escape to cancel.\n{}", self.editor_message)
+                )
+                .scale(graphics::Scale { x: 18.0, y: 18.0 }));
+                message.set_bounds(ggez::mint::Point2 { x: SCREEN_SIZE.0 * 0.25 - 10f32, y: f32::INFINITY }, graphics::Align::Left);
+                graphics::draw(ctx, &message, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: SCREEN_SIZE.0 * 0.75,
+                        y: GRID_CELL_SIZE.1 as f32 * 9f32,
+                    }));
+            }
+
+            // draw score if relevant
+            if (self.screen == ScreenState::ScoreScreen) {
+            let cover_rectangle = graphics::Mesh::new_rectangle(ctx, 
+                graphics::DrawMode::fill(), 
+                graphics::Rect::new_i32(
+                    0,
+                    0,
+                    SCREEN_SIZE.0 as i32,
+                    SCREEN_SIZE.1 as i32,
+                ), SEMI_OPAQUE_GREY)?;
+            graphics::draw(ctx, &cover_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+            
+            let mut score_text;
+            if self.cur_winner == None {
+                score_text = graphics::Text::new(
+                        graphics::TextFragment::from(format!("No one wins!")
+                    )
+                    .scale(graphics::Scale { x: 60.0, y: 60.0 }));
+            } else {
+                score_text = graphics::Text::new(
+                        graphics::TextFragment::from(match (&self.bughouse, self.cur_winner.unwrap()) {
+                            (Some(_), Colour::White) => format!("White A & Black B win!"),
+                            (Some(_), Colour::Black) => format!("Black A & White B win!"),
+                            (None, Colour::White) => format!("{} wins!", self.players[0]),
+                            (None, Colour::Black) => format!("{} wins!", self.players[1]),
+                        }
+                    )
+                    .scale(graphics::Scale { x: 60.0, y: 60.0 }));
+            }
+            let text_dim = score_text.dimensions(ctx); 
+            graphics::draw(ctx, &score_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: (SCREEN_SIZE.0 - text_dim.0 as f32) * 0.5,
+                    y: (SCREEN_SIZE.1 - text_dim.1 as f32) * 0.5,
+                }));
+
+            let series_text = graphics::Text::new(
+                    graphics::TextFragment::from(if self.bughouse.is_some() {
+                        format!("White A & Black B {} - {} Black A & White B", self.wins.0, self.wins.1)
+                    } else {
+                        format!("{} {} - {} {}", self.players[0], self.wins.0, self.wins.1, self.players[1])
+                    }
+                )
+                .scale(graphics::Scale { x: 30.0, y: 30.0 }));
+            let series_dim = series_text.dimensions(ctx);
+            graphics::draw(ctx, &series_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: (SCREEN_SIZE.0 - series_dim.0 as f32) * 0.5,
+                    y: (SCREEN_SIZE.1 + text_dim.1 as f32) * 0.5,
+                }));
+
+            let mut hints = Vec::new();
+            if let ([Some(white), Some(black)], Some(change)) = (self.seats, self.rating_change) {
+                hints.push(format!("Ratings: {} {:.0} ({:+.0}), {} {:.0} ({:+.0})",
+                    self.profiles[white].name, self.profiles[white].rating, change,
+                    self.profiles[black].name, self.profiles[black].rating, -change));
+            }
+            if self.cur_winner != None && !self.win_spent {
+                hints.push(format!("{}: left-click one of {}'s mods to strip it (costs your win)", self.side_label(self.cur_winner.unwrap()), self.side_label(opposite(self.cur_winner.unwrap()))));
+            }
+            if !self.swapped.0 {
+                hints.push(format!("{}: right-click one of your mods to swap it for a fresh roll", self.side_label(Colour::White)));
+            }
+            if !self.swapped.1 {
+                hints.push(format!("{}: right-click one of your mods to swap it for a fresh roll", self.side_label(Colour::Black)));
+            }
+            hints.push(format!("Click anywhere else to continue"));
+            for (i, hint) in hints.iter().enumerate() {
+                let hint_text = graphics::Text::new(
+                        graphics::TextFragment::from(hint.clone()
+                    )
+                    .scale(graphics::Scale { x: 18.0, y: 18.0 }));
+                let hint_dim = hint_text.dimensions(ctx);
+                graphics::draw(ctx, &hint_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: (SCREEN_SIZE.0 - hint_dim.0 as f32) * 0.5,
+                        y: (SCREEN_SIZE.1 + text_dim.1 as f32) * 0.5 + series_dim.1 as f32 + 22f32 * (i + 1) as f32,
+                    }));
+            }
+            }
+        }
+        
+        if self.screen == ScreenState::ModScreen {
+            let cur_loser = opposite(self.cur_winner.unwrap());
+
+            // draw boxes for the choices, each with its own banish button.
+            let banishes_left = MAX_BANISHES.saturating_sub(if cur_loser == Colour::White { self.white_banished.len() } else { self.black_banished.len() });
+            for i in 0..self.random_mods.len() {
+                let offer = mod_offer_rect(i);
+                let inner_box = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), offer, [1.0, 1.0, 1.0, 1.0].into())?;
+                let bounding_box = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(3f32), offer, [0.0, 0.0, 0.0, 1.0].into())?;
+                graphics::draw(ctx, &inner_box, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+                graphics::draw(ctx, &bounding_box, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+
+                // an empty slot means every mod left is held, banished or ruled out by the mods in play
+                let modi = match self.random_mods[i] {
+                    Some(modi) => modi,
+                    None => {
+                        let empty_text = graphics::Text::new(
+                            graphics::TextFragment::from(format!("No mod left to offer")
+                        )
+                        .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+                        graphics::draw(ctx, &empty_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                            .dest(ggez::mint::Point2 {
+                                x: GRID_CELL_SIZE.0 as f32 * 2f32,
+                                y: offer.y + GRID_CELL_SIZE.1 as f32 * 0.5,
+                            }));
+                        continue;
+                    },
+                };
+
+                // Draw different text for each choice
+                let mod_text = graphics::Text::new(
+                    graphics::TextFragment::from(modi.display_name(cur_loser)
+                )
+                .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+                graphics::draw(ctx, &mod_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: GRID_CELL_SIZE.0 as f32 * 2f32,
+                        y: offer.y + GRID_CELL_SIZE.1 as f32 * 0.5,
+                    }));
+                graphics::draw(ctx, &self.sprites[&modi.get_piece(cur_loser)], (ggez::mint::Point2 { x: GRID_CELL_SIZE.0 as f32, y: offer.y + GRID_CELL_SIZE.1 as f32 * 0.5 }, ));
+
+                let banish = mod_banish_rect(i);
+                let banish_box = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), banish, if banishes_left > 0 { WHITE_RED } else { SEMI_OPAQUE_GREY })?;
+                graphics::draw(ctx, &banish_box, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+                let banish_text = graphics::Text::new(
+                    graphics::TextFragment::from(format!("Banish")
+                )
+                .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+                graphics::draw(ctx, &banish_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: banish.x + GRID_CELL_SIZE.0 as f32 * 0.5,
+                        y: banish.y + GRID_CELL_SIZE.1 as f32 * 0.25,
+                    }));
+            }
+
+            // draw reroll and skip buttons below the choices
+            let rerolls_left = if cur_loser == Colour::White { self.rerolls.0 } else { self.rerolls.1 };
+            let reroll_colour = if rerolls_left > 0 { BLACK } else { SEMI_OPAQUE_GREY };
+            let buttons = [
+                (mod_reroll_rect(), reroll_colour, format!("Reroll ({} left)", rerolls_left)),
+                (mod_skip_rect(), BLACK, format!("Skip (+1 reroll)")),
+            ];
+            for (rect, colour, label) in buttons.iter() {
+                let button = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), *rect, *colour)?;
+                graphics::draw(ctx, &button, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+                let text = graphics::Text::new(
+                    graphics::TextFragment::from(label.clone()
+                )
+                .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+                graphics::draw(ctx, &text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: rect.x + GRID_CELL_SIZE.0 as f32 * 0.5,
+                        y: rect.y + GRID_CELL_SIZE.1 as f32 * 0.25,
+                    }));
+            }
+
+            let banished_text = graphics::Text::new(
+                graphics::TextFragment::from(format!("{} has {} banish(es) left", self.side_label(cur_loser), banishes_left)
+            )
+            .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+            graphics::draw(ctx, &banished_text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: SCREEN_SIZE.0 * 0.5,
+                    y: mod_reroll_rect().y + GRID_CELL_SIZE.1 as f32 * 0.25,
+                }));
+        }
+        if self.screen == ScreenState::HelpScreen {
+            let pages = (help_examples().len() + HELP_PER_PAGE - 1) / HELP_PER_PAGE;
+            let title = graphics::Text::new(
+                graphics::TextFragment::from(format!("Mods, page {}/{} (click one to try it, Left/Right to turn the page, H or Escape to go back)", self.help_page + 1, pages)
+            )
+            .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+            graphics::draw(ctx, &title, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: GRID_CELL_SIZE.0 as f32 * 0.5,
+                    y: GRID_CELL_SIZE.1 as f32 * 0.25,
+                }));
+
+            let entry_size = (SCREEN_SIZE.0 * 0.5, (SCREEN_SIZE.1 - GRID_CELL_SIZE.1 as f32) / 3f32);
+            for (i, modi) in help_examples().iter().skip(self.help_page * HELP_PER_PAGE).take(HELP_PER_PAGE).enumerate() {
+                let origin = (GRID_CELL_SIZE.0 as f32 * 0.5 + entry_size.0 * (i % 2) as f32,
+                              GRID_CELL_SIZE.1 as f32 + entry_size.1 * (i / 2) as f32);
+
+                // draw the example diagram
+                let (pieces, highlighted) = modi.diagram();
+                for col in 0..3u8 {
+                    for row in 0..3u8 {
+                        let light = (col + row) % 2 == 0;
+                        let colour = match (highlighted.contains(&(col, row)), light) {
+                            (true, true) => BLACK_RED,
+                            (true, false) => WHITE_RED,
+                            (false, true) => BLACK,
+                            (false, false) => WHITE,
+                        };
+                        let tile = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(),
+                            graphics::Rect::new(origin.0 + (col as i16 * GRID_CELL_SIZE.0) as f32,
+                                                origin.1 + (row as i16 * GRID_CELL_SIZE.1) as f32,
+                                                GRID_CELL_SIZE.0 as f32,
+                                                GRID_CELL_SIZE.1 as f32),
+                            colour)?;
+                        graphics::draw(ctx, &tile, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+                    }
+                }
+                for ((col, row), piece) in pieces.iter() {
+                    graphics::draw(ctx, &self.sprites[piece], (ggez::mint::Point2 { x: origin.0 + (*col as i16 * GRID_CELL_SIZE.0) as f32, y: origin.1 + (*row as i16 * GRID_CELL_SIZE.1) as f32 }, ));
+                }
+
+                // and the rules next to it
+                let mut text = graphics::Text::new(
+                    graphics::TextFragment::from(format!("{}\n{}", modi.to_string(), modi.description())
+                )
+                .scale(graphics::Scale { x: 18.0, y: 18.0 }));
+                text.set_bounds(ggez::mint::Point2 { x: entry_size.0 - GRID_CELL_SIZE.0 as f32 * 4.5, y: f32::INFINITY }, graphics::Align::Left);
+                graphics::draw(ctx, &text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: origin.0 + GRID_CELL_SIZE.0 as f32 * 3.5,
+                        y: origin.1,
+                    }));
+            }
+        }
+
+        if self.screen == ScreenState::ProfileScreen {
+            let title = graphics::Text::new(
+                graphics::TextFragment::from(format!("Players: click a profile to seat it, or type a name and press Enter to add one")
+            )
+            .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+            graphics::draw(ctx, &title, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: GRID_CELL_SIZE.0 as f32 * 0.5,
+                    y: GRID_CELL_SIZE.1 as f32 * 0.25,
+                }));
+            let name = |seat: Option<usize>, guest: &str| seat.map_or(format!("{} (guest)", guest), |i| self.profiles[i].name.clone());
+            let status = graphics::Text::new(
+                graphics::TextFragment::from(format!("White: {}, Black: {}. Enter starts the series. Tab: mods held count towards ratings ({}).\n{}",
+                    name(self.seats[0], "White"), name(self.seats[1], "Black"), if self.rate_mods { "on" } else { "off" },
+                    if self.new_profile.is_empty() { String::new() } else { format!("New profile: {}_", self.new_profile) })
+            )
+            .scale(graphics::Scale { x: 18.0, y: 18.0 }));
+            graphics::draw(ctx, &status, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: GRID_CELL_SIZE.0 as f32 * 0.5,
+                    y: GRID_CELL_SIZE.1 as f32 * 0.9,
+                }));
+
+            for (i, profile) in self.profiles.iter().take(PROFILE_ROWS).enumerate() {
+                let seat = self.seats.iter().position(|seat| *seat == Some(i));
+                let row = profile_row_rect(i);
+                let row_box = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), row,
+                    if seat.is_some() { [0.8, 0.95, 0.8, 1.0].into() } else { [0.95, 0.95, 0.95, 1.0].into() })?;
+                graphics::draw(ctx, &row_box, DrawParam::default());
+                let set_box = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), profile_set_rect(i), profile.set.tint())?;
+                graphics::draw(ctx, &set_box, DrawParam::default());
+                let side_box = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(1f32), profile_side_rect(i), [0.0, 0.0, 0.0, 1.0].into())?;
+                graphics::draw(ctx, &side_box, DrawParam::default());
+
+                let texts = [
+                    (row.x, format!("{}  {:.0} after {} games{}", profile.name, profile.rating, profile.games, match seat {
+                        Some(0) => ", plays White",
+                        Some(_) => ", plays Black",
+                        None => "",
+                    })),
+                    (profile_set_rect(i).x, format!("Pieces: {}", profile.set.name())),
+                    (profile_side_rect(i).x, format!("Prefers {:?}", profile.side)),
+                ];
+                for (x, line) in texts.iter() {
+                    let text = graphics::Text::new(
+                        graphics::TextFragment::from(line.clone()
+                    )
+                    .scale(graphics::Scale { x: 18.0, y: 18.0 }));
+                    graphics::draw(ctx, &text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                        .dest(ggez::mint::Point2 {
+                            x: x + 5f32,
+                            y: row.y + 4f32,
+                        }));
+                }
+            }
+        }
+
+        if self.screen == ScreenState::StatsScreen {
+            let title = graphics::Text::new(
+                graphics::TextFragment::from(format!("Statistics over {} recorded games (S or Escape to go back)", self.history.len())
+            )
+            .scale(graphics::Scale { x: 20.0, y: 20.0 }));
+            graphics::draw(ctx, &title, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: GRID_CELL_SIZE.0 as f32 * 0.5,
+                    y: GRID_CELL_SIZE.1 as f32 * 0.25,
+                }));
+
+            let mut mod_lines = vec![format!("Win rate per mod")];
+            mod_lines.extend(mod_win_rates(&self.history).iter().take(24).map(|(name, wins, games)| {
+                format!("{}: {}% of {}", name, wins * 100 / games, games)
+            }));
+
+            let series = longest_series(&self.history);
+            let mut series_lines = vec![format!("Longest series")];
+            if let Some(first) = series.first() {
+                series_lines.push(format!("{} games, started {}", series.len(), first.date));
+                for (a, b, a_wins, b_wins, draws) in head_to_head(&series).iter() {
+                    series_lines.push(format!("{} {} - {} {}, {} drawn", a, a_wins, b_wins, b, draws));
+                }
+            }
+
+            let mut player_lines = vec![format!("Head to head")];
+            player_lines.extend(head_to_head(&self.history).iter().map(|(a, b, a_wins, b_wins, draws)| {
+                format!("{} {} - {} {}, {} drawn", a, a_wins, b_wins, b, draws)
+            }));
+
+            for (i, lines) in [mod_lines, series_lines, player_lines].iter().enumerate() {
+                let mut text = graphics::Text::new(
+                    graphics::TextFragment::from(lines.join("\n")
+                )
+                .scale(graphics::Scale { x: 16.0, y: 16.0 }));
+                text.set_bounds(ggez::mint::Point2 { x: SCREEN_SIZE.0 / 3f32 - GRID_CELL_SIZE.0 as f32 * 0.5, y: f32::INFINITY }, graphics::Align::Left);
+                graphics::draw(ctx, &text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest(ggez::mint::Point2 {
+                        x: GRID_CELL_SIZE.0 as f32 * 0.5 + SCREEN_SIZE.0 / 3f32 * i as f32,
+                        y: GRID_CELL_SIZE.1 as f32,
+                    }));
+            }
+        }
+
+        // draw a tooltip for the hovered mod
+        if let Some(modi) = self.hovered_mod() {
+            let mut text = graphics::Text::new(
+                graphics::TextFragment::from(modi.description()
+            )
+            .scale(graphics::Scale { x: 18.0, y: 18.0 }));
+            text.set_bounds(ggez::mint::Point2 { x: GRID_CELL_SIZE.0 as f32 * 6f32, y: f32::INFINITY }, graphics::Align::Left);
+            let text_dim = text.dimensions(ctx);
+            // keep the tooltip on screen
+            let tip_x = self.mouse_pos.0.min(SCREEN_SIZE.0 - text_dim.0 as f32 - 10f32) + 5f32;
+            let tip_y = (self.mouse_pos.1 + 20f32).min(SCREEN_SIZE.1 - text_dim.1 as f32 - 10f32);
+            let tip_box = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(),
+                graphics::Rect::new(tip_x - 5f32, tip_y - 5f32, text_dim.0 as f32 + 10f32, text_dim.1 as f32 + 10f32),
+                [1.0, 1.0, 0.9, 1.0].into())?;
+            let tip_border = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(1f32),
+                graphics::Rect::new(tip_x - 5f32, tip_y - 5f32, text_dim.0 as f32 + 10f32, text_dim.1 as f32 + 10f32),
+                [0.0, 0.0, 0.0, 1.0].into())?;
+            graphics::draw(ctx, &tip_box, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+            graphics::draw(ctx, &tip_border, (ggez::mint::Point2 { x: 0.0, y: 0.0 }, ));
+            graphics::draw(ctx, &text, DrawParam::default().color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: tip_x,
+                    y: tip_y,
+                }));
+        }
+
+        // render updated graphics
+        graphics::present(ctx)?;
+
+        Ok(())
+    }
+
+    /// Track the cursor for tooltips
+    fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        self.mouse_pos = (x, y);
+    }
+
+    /// Keyboard shortcuts. Escape backs out of the help, statistics, tutorial and editor screens and quits from the rest,
+    /// as it does by default in ggez
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods, _repeat: bool) {
+        match keycode {
+            KeyCode::Return if self.screen == ScreenState::ProfileScreen => {
+                if self.new_profile.is_empty() {
+                    self.start_series();
+                } else {
+                    self.add_profile();
+                }
+            },
+            KeyCode::Back if self.screen == ScreenState::ProfileScreen => {
+                self.new_profile.pop();
+            },
+            KeyCode::Escape if self.screen == ScreenState::ProfileScreen && !self.new_profile.is_empty() => {
+                self.new_profile = String::new();
+            },
+            KeyCode::Tab if self.screen == ScreenState::ProfileScreen => {
+                self.rate_mods = !self.rate_mods;
+            },
+            KeyCode::H if self.screen != ScreenState::HelpScreen && self.screen != ScreenState::ProfileScreen => {
+                self.help_return = self.screen;
+                self.screen = ScreenState::HelpScreen;
+            },
+            KeyCode::H | KeyCode::Escape if self.screen == ScreenState::HelpScreen => {
+                self.screen = self.help_return;
+            },
+            KeyCode::S if keymods.contains(KeyMods::CTRL) && self.screen == ScreenState::GameScreen && self.tutorial.is_none() && self.bughouse.is_none() => {
+                self.save_game();
+            },
+            KeyCode::L if keymods.contains(KeyMods::CTRL) && self.screen == ScreenState::GameScreen && self.tutorial.is_none() && self.bughouse.is_none() => {
+                self.load_game();
+            },
+            KeyCode::U if self.screen == ScreenState::GameScreen && self.tutorial.is_none() && self.bughouse.is_none() => {
+                self.undo();
+            },
+            KeyCode::S if self.screen == ScreenState::GameScreen || self.screen == ScreenState::ScoreScreen || self.screen == ScreenState::ModScreen => {
+                self.stats_return = self.screen;
+                self.screen = ScreenState::StatsScreen;
+            },
+            KeyCode::S | KeyCode::Escape if self.screen == ScreenState::StatsScreen => {
+                self.screen = self.stats_return;
+            },
+            KeyCode::Left if self.screen == ScreenState::HelpScreen => {
+                self.help_page = self.help_page.saturating_sub(1);
+            },
+            KeyCode::Right if self.screen == ScreenState::HelpScreen => {
+                if (self.help_page + 1) * HELP_PER_PAGE < help_examples().len() {
+                    self.help_page += 1;
+                }
+            },
+            KeyCode::A | KeyCode::R | KeyCode::D if self.screen == ScreenState::GameScreen && self.tutorial.is_none() && self.move_log.is_empty() => {
+                let variant = match keycode {
+                    KeyCode::A => Variant::Antichess,
+                    KeyCode::R => Variant::RacingKings,
+                    _ => Variant::Duck,
+                };
+                self.toggle_series_variant(variant);
+            },
+            KeyCode::C if self.screen == ScreenState::GameScreen && self.tutorial.is_none() && self.move_log.is_empty() && self.bughouse.is_none() => {
+                self.alternate = !self.alternate;
+                // guests go by their colour, which stops working as a name once colours change hands
+                if self.alternate {
+                    for (seat, colour) in ["White", "Black"].iter().enumerate() {
+                        if self.players[seat] == *colour {
+                            self.players[seat] = format!("Player {}", seat + 1);
+                        }
+                    }
+                }
+            },
+            KeyCode::B if self.screen == ScreenState::GameScreen && self.tutorial.is_none() && self.move_log.is_empty() => {
+                if self.bughouse.as_ref().map_or(true, |bughouse| bughouse.link.is_none()) {
+                    self.bughouse = if self.bughouse.is_some() { None } else { Some(Bughouse::new(None, false)) };
+                    // bughouse trays fill up from the partner board, so they start out empty
+                    if self.bughouse.is_some() {
+                        self.taken_black_pieces = Vec::new();
+                        self.taken_white_pieces = Vec::new();
+                    }
+                    self.start_fen = self.position_fen();
+                }
+            },
+            KeyCode::P if self.screen == ScreenState::GameScreen || self.screen == ScreenState::ScoreScreen => {
+                if let Err(e) = std::fs::write("game.pgn", self.game_pgn()) {
+                    println!("Couldn't save game.pgn: {}", e);
+                }
+            },
+            KeyCode::N if self.screen == ScreenState::GameScreen && self.tutorial.is_some() => {
+                let next = self.tutorial.as_ref().unwrap().lesson + 1;
+                if next < lessons().len() {
+                    self.start_tutorial(next);
+                } else {
+                    self.end_tutorial();
+                }
+            },
+            KeyCode::Escape if self.screen == ScreenState::GameScreen && self.tutorial.is_some() => {
+                self.end_tutorial();
+            },
+            // only before the first move, so a game in progress can't be rewritten
+            KeyCode::E if self.screen == ScreenState::GameScreen && self.tutorial.is_none() && self.move_log.is_empty() && self.bughouse.is_none() => {
+                self.editor_backup = Some(self.snapshot());
+                self.screen = ScreenState::EditorScreen;
+                self.selected_pos = (0, 0);
+                self.highlighted_pos = Vec::new();
+                self.editor_message = String::new();
+            },
+            KeyCode::M if self.screen == ScreenState::GameScreen && !self.duck_pending => {
+                self.toggle_double_move(self.board.active_color);
+            },
+            KeyCode::E if self.screen == ScreenState::EditorScreen => {
+                self.leave_editor();
+            },
+            KeyCode::Escape if self.screen == ScreenState::EditorScreen => {
+                if let Some(backup) = self.editor_backup.take() {
+                    self.restore(backup);
+                }
+                self.screen = ScreenState::GameScreen;
+            },
+            KeyCode::Escape => event::quit(ctx),
+            _ => (),
+        }
+    }
+
+    /// Type in the name of a new profile
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) {
+        if self.screen == ScreenState::ProfileScreen && (character.is_alphanumeric() || character == ' ' || character == '-') && self.new_profile.chars().count() < 20 {
+            self.new_profile.push(character);
+        }
+    }
+
+    /// Update game on mouse click
+    fn mouse_button_up_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        self.click(button, x, y);
+    }
+}
+
+impl AppState {
+    /// Advances the game by `elapsed` seconds: clocks and animations tick, and any game that is over by
+    /// the rules of the mods in play is ended. The simulator steps games this way too.
+    fn step(&mut self, elapsed: f32) {
+        if self.screen == ScreenState::GameScreen && self.tutorial.is_none() {
+            self.update_bughouse(elapsed);
+        }
+        for explosion in self.explosions.iter_mut() {
+            explosion.1 -= elapsed;
+        }
+        self.explosions.retain(|explosion| explosion.1 > 0.0);
+
+        // atomic games are over once the side to move is out of legal moves, by mate or stalemate
+        if self.atomic_game() && self.screen == ScreenState::GameScreen && !self.atomic_has_moves() {
+            let col = self.board.active_color;
+            if self.atomic_in_check(&self.board.board, col) {
+                self.end_game(Some(opposite(col)), Termination::Checkmate);
+            } else {
+                self.end_game(None, Termination::Stalemate);
+            }
+        }
+
+        // in antichess the side that can't move, usually for lack of pieces, wins
+        if self.variant == Variant::Antichess && self.screen == ScreenState::GameScreen && !self.antichess_has_moves() {
+            self.end_game(Some(self.board.active_color), Termination::NoMoves);
+        }
+
+        // so does a side stuck behind the duck
+        if self.variant == Variant::Duck && self.screen == ScreenState::GameScreen && !self.duck_pending && !self.duck_has_moves() {
+            self.end_game(Some(self.board.active_color), Termination::NoMoves);
+        }
+
+        if self.screen == ScreenState::GameScreen {
+            self.check_goals();
+        }
+
+        // the same position, pockets and side to move included, for the third time is a draw
+        if self.screen == ScreenState::GameScreen && self.position_history.last().map_or(false, |last| self.position_history.iter().filter(|fen| *fen == last).count() >= 3) {
+            self.end_game(None, Termination::Repetition);
+        }
+
+        // portals and fairies can open an escape, or a check, the library doesn't see
+        if self.layered() && !self.atomic_game() && self.screen == ScreenState::GameScreen && !self.layered_has_moves() {
+            if self.layered_in_check() {
+                self.end_game(Some(opposite(self.board.active_color)), Termination::Checkmate);
+            } else {
+                self.end_game(None, Termination::Stalemate);
+            }
+        }
+
+        // the library counts bouncing off a shield as a way out of check
+        if !self.shields.is_empty() && self.variant == Variant::Standard && !self.atomic_game() && !self.layered() && self.screen == ScreenState::GameScreen
+            && self.board.get_game_state() == GameState::Check && !self.shield_has_moves() {
+            self.end_game(Some(opposite(self.board.active_color)), Termination::Checkmate);
+        }
+
+        if self.variant != Variant::Antichess && self.variant != Variant::Duck && !self.atomic_game() && !self.layered() && self.board.get_game_state() == GameState::CheckMate && self.screen == ScreenState::GameScreen && !self.drop_escapes() && !self.teleport_escapes() {
+            match self.board.active_color {
+                Colour::White => {
+                    self.end_game(Some(Colour::Black), Termination::Checkmate);
+                },
+                Colour::Black => {
+                    self.end_game(Some(Colour::White), Termination::Checkmate);
+                }
+            }
+        }
+
+        // armies that must be wiped out lose once only their king is left
+        if self.screen == ScreenState::GameScreen {
+            for col in [Colour::White, Colour::Black].iter() {
+                let mods = if *col == Colour::White { &self.white_mods } else { &self.black_mods };
+                let wipeout = mods.iter().any(|modi| if let Mods::Army(army) = modi { armies()[*army].wipeout } else { false });
+                if wipeout && !self.board.board.values().any(|piece| piece.colour() == *col && *piece != PieceType::King(*col)) {
+                    self.end_game(Some(opposite(*col)), Termination::Wipeout);
+                }
+            }
+        }
+
+        unsafe { seed += 1; }
+    }
+
+    /// Handles a click at the given screen coordinates. The simulator's bots play through here as well,
+    /// so they follow exactly the same rules as players do.
+    fn click(&mut self, button: MouseButton, x: f32, y: f32) {
+        // the game from before any move the click plays, for undo
+        let undoable = self.screen == ScreenState::GameScreen && self.tutorial.is_none() && self.bughouse.is_none();
+        let before = if undoable { Some(self.snapshot()) } else { None };
+        let moves = self.move_log.len();
+        self.handle_click(button, x, y);
+        if let Some(before) = before {
+            if self.move_log.len() > moves {
+                self.undo_stack.push(before);
+            }
+        }
+    }
+
+    fn handle_click(&mut self, button: MouseButton, x: f32, y: f32) {
+        if self.screen == ScreenState::GameScreen && self.waiting_for_partner() {
+            return;
+        }
+        if self.screen == ScreenState::EditorScreen {
+            self.editor_click(button, x, y);
+        } else if button == MouseButton::Left && self.screen == ScreenState::ProfileScreen {
+            self.profile_click(x, y);
+        } else if button == MouseButton::Left && self.screen == ScreenState::HelpScreen {
+            let entry_size = (SCREEN_SIZE.0 * 0.5, (SCREEN_SIZE.1 - GRID_CELL_SIZE.1 as f32) / 3f32);
+            let col = ((x - GRID_CELL_SIZE.0 as f32 * 0.5) / entry_size.0).floor();
+            let row = ((y - GRID_CELL_SIZE.1 as f32) / entry_size.1).floor();
+            if col >= 0.0 && col < 2.0 && row >= 0.0 && row < 3.0 {
+                let example = help_examples().get(self.help_page * HELP_PER_PAGE + (row * 2.0 + col) as usize).cloned();
+                if let Some(lesson) = example.and_then(|modi| lessons().iter().position(|lesson| lesson.modi == modi)) {
+                    self.start_tutorial(lesson);
+                }
+            }
+        } else if button == MouseButton::Left && self.screen == ScreenState::ModScreen {
+            let cur_loser = opposite(self.cur_winner.unwrap());
+            let point = ggez::mint::Point2 { x: x, y: y };
+
+            for i in 0..self.random_mods.len() {
+                let modi = match self.random_mods[i] {
+                    Some(modi) => modi,
+                    None => continue,
+                };
+                // the banish button sits inside the offer box, so it must be checked first
+                if mod_banish_rect(i).contains(point) {
+                    let banished = if cur_loser == Colour::White { &mut self.white_banished } else { &mut self.black_banished };
+                    if banished.len() < MAX_BANISHES {
+                        banished.insert(modi);
+                        self.random_mods[i] = self.roll_mod_offer();
+                    }
+                    return;
+                }
+                if mod_offer_rect(i).contains(point) {
+                    if cur_loser == Colour::White {
+                        self.white_mods.insert(modi);
+                    } else {
+                        self.black_mods.insert(modi);
+                    }
+                    self.start_next_game();
+                    return;
+                }
+            }
+
+            if mod_reroll_rect().contains(point) {
+                let rerolls_left = if cur_loser == Colour::White { &mut self.rerolls.0 } else { &mut self.rerolls.1 };
+                if *rerolls_left > 0 {
+                    *rerolls_left -= 1;
+                    self.roll_mod_offers();
+                }
+            } else if mod_skip_rect().contains(point) {
+                if cur_loser == Colour::White {
+                    self.rerolls.0 += 1;
+                } else {
+                    self.rerolls.1 += 1;
+                }
+                self.start_next_game();
+            }
+        } else if button == MouseButton::Right && self.screen == ScreenState::GameScreen && !self.tutorial.as_ref().map_or(false, |tutorial| tutorial.done) {
+            // right-clicking the king offers the squares it can teleport to
+            if let Some((file, rank)) = board_square_at(x, y) {
+                let (pos_x, pos_y) = (file as f32, rank as f32);
+                if self.board.board.get(&Position { file: pos_x as u8, rank: pos_y as u8 }) == Some(&PieceType::King(self.board.active_color)) {
+                    let squares = self.teleport_squares();
+                    if !squares.is_empty() {
+                        self.teleporting = true;
+                        self.selected_pos = (pos_x as isize, pos_y as isize);
+                        self.highlighted_pos = squares.into_iter().map(|(file, rank)| (file as isize, rank as isize)).collect();
+                    }
+                }
+            }
+        } else if button == MouseButton::Right && self.screen == ScreenState::ScoreScreen {
+            if let Some((col, modi)) = self.mod_row_at(x, y) {
+                self.swap_mod(col, modi);
+            }
+        } else if button == MouseButton::Left && self.screen == ScreenState::ScoreScreen {
+            if let Some((col, modi)) = self.mod_row_at(x, y) {
+                self.strip_mod(col, modi);
+                return;
+            }
+            if self.cur_winner == None {
+                self.start_next_game();
+                return;
+            }
+            self.screen = ScreenState::ModScreen;
+            self.roll_mod_offers();
+        } else if button == MouseButton::Left && self.screen == ScreenState::GameScreen {
+            if self.tutorial.as_ref().map_or(false, |tutorial| tutorial.done) {
+                return;
+            }
+            if let Some((col, Mods::DoubleMove)) = self.mod_row_at(x, y) {
+                self.toggle_double_move(col);
+                return;
+            }
+            // on a shared machine, clicking the partner board moves play over to it
+            if partner_board_rect().contains(ggez::mint::Point2 { x: x, y: y }) {
+                if self.bughouse.as_ref().map_or(false, |bughouse| bughouse.link.is_none()) && !self.duck_pending {
+                    self.swap_boards();
+                }
+                return;
+            }
+            if let Some((file, rank)) = board_square_at(x, y) {
+                let (pos_x, pos_y) = (file as f32, rank as f32);
+
+                // second half of a duck chess turn
+                if self.duck_pending {
+                    self.place_duck((pos_x as u8, pos_y as u8));
+                    return;
+                }
+
+                if self.teleporting {
+                    self.teleporting = false;
+                    if self.highlighted_pos.contains(&(pos_x as isize, pos_y as isize)) && self.lesson_allows(self.selected_pos, (pos_x as isize, pos_y as isize)) {
+                        self.teleport((pos_x as u8, pos_y as u8));
+                    }
+                    self.selected_pos = (0, 0);
+                    self.highlighted_pos = Vec::new();
+                    return;
+                }
+
+                if self.highlighted_pos.contains(&(pos_x as isize, pos_y as isize)) {
+                    if !self.lesson_allows(self.selected_pos, (pos_x as isize, pos_y as isize)) {
+                        return;
+                    }
+                    let notation = self.move_notation(self.selected_pos, (pos_x as isize, pos_y as isize));
+
+                    // a shield takes the hit, Sniper shots and atomic captures included, and the attacker stays put
+                    if self.selected_pos.1 <= 8 && self.capture_hooks((self.selected_pos.0 as u8, self.selected_pos.1 as u8), (pos_x as u8, pos_y as u8)).bounced {
+                        self.break_shield((self.selected_pos.0 as u8, self.selected_pos.1 as u8), (pos_x as u8, pos_y as u8));
+                        self.log_move(notation);
+                        self.end_move();
+                        if self.variant == Variant::Duck {
+                            self.duck_pending = true;
+                        }
+                        self.selected_pos = (0, 0);
+                        self.highlighted_pos = Vec::new();
+                        return;
+                    }
+
+                    if self.variant == Variant::Antichess && self.selected_pos.1 <= 8 {
+                        self.play_plain_move((self.selected_pos.0 as u8, self.selected_pos.1 as u8), (pos_x as u8, pos_y as u8));
+                        self.log_move(notation);
+                        self.end_move();
+                        self.selected_pos = (0, 0);
+                        self.highlighted_pos = Vec::new();
+                        return;
+                    }
+
+                    // atomic, portal and fairy moves the library doesn't know are played here
+                    if (self.atomic_game() || self.layered()) && self.selected_pos.1 <= 8 && !self.board.get_possible_moves(Position { file: self.selected_pos.0 as u8, rank: self.selected_pos.1 as u8 }.to_string()).unwrap_or_default().contains(&Position { file: pos_x as u8, rank: pos_y as u8 }.to_string()) {
+                        let (from, to) = ((self.selected_pos.0 as u8, self.selected_pos.1 as u8), (pos_x as u8, pos_y as u8));
+                        let target = self.board.board.get(&Position { file: to.0, rank: to.1 }).copied();
+                        let hooks = self.capture_hooks(from, to);
+                        self.play_plain_move(from, to);
+                        if let Some(target) = target {
+                            if hooks.sniper {
+                                if let Some(piece) = self.board.board.remove(&Position { file: to.0, rank: to.1 }) {
+                                    self.board.board.insert(Position { file: from.0, rank: from.1 }, piece);
+                                    self.move_layers(to, from);
+                                    self.track_promotion(to, from, piece);
+                                }
+                            }
+                            self.after_capture(hooks, from, to, target);
+                        }
+                        self.log_move(notation);
+                        self.end_move();
+                        self.selected_pos = (0, 0);
+                        self.highlighted_pos = Vec::new();
+                        return;
+                    }
+
+                    if self.variant == Variant::Duck && self.selected_pos.1 <= 8 {
+                        let mover = self.board.active_color;
+                        if self.board.board.get(&Position { file: pos_x as u8, rank: pos_y as u8 }) == Some(&PieceType::Rook(mover)) {
+                            self.castle(mover, pos_x as isize > self.selected_pos.0);
+                        } else {
+                            self.play_plain_move((self.selected_pos.0 as u8, self.selected_pos.1 as u8), (pos_x as u8, pos_y as u8));
+                        }
+                        self.log_move(notation);
+                        self.end_move();
+                        self.selected_pos = (0, 0);
+                        self.highlighted_pos = Vec::new();
+                        if !self.board.board.values().any(|piece| *piece == PieceType::King(opposite(mover))) {
+                            self.end_game(Some(mover), Termination::KingCaptured);
+                        } else {
+                            self.duck_pending = true;
+                        }
+                        return;
+                    }
+
+                    // Chess960 castling is played by moving the king onto its own rook
+                    if self.board.board.get(&Position { file: pos_x as u8, rank: pos_y as u8 }) == Some(&PieceType::Rook(self.board.active_color)) {
+                        self.castle(self.board.active_color, pos_x as isize > self.selected_pos.0);
+                        self.log_move(notation);
+                        self.end_move();
+                        self.selected_pos = (0, 0);
+                        self.highlighted_pos = Vec::new();
+                        return;
+                    }
+                    let mut taking_move = false;
+                    if self.board.board.contains_key(&Position { file: pos_x as u8, rank: pos_y as u8 }) && self.board.board[&Position { file: self.selected_pos.0 as u8, rank: self.selected_pos.1 as u8}].colour() == self.board.active_color {
+                        taking_move = true;
+                        match self.board.board[&Position { file: pos_x as u8, rank: pos_y as u8 }] {
+                            PieceType::Queen(colour) => match colour {
+                                Colour::Black => self.taken_black_pieces.push(PieceType::Queen(Colour::Black)),
+                                _ => self.taken_white_pieces.push(PieceType::Queen(Colour::White))
+                            },
+                            PieceType::King(colour) => match colour {
+                                Colour::Black => self.taken_black_pieces.push(PieceType::King(Colour::Black)),
+                                _ => self.taken_white_pieces.push(PieceType::King(Colour::White))
+                            },
+                            PieceType::Pawn(colour) => match colour {
+                                Colour::Black => self.taken_black_pieces.push(PieceType::Pawn(Colour::Black)),
+                                _ => self.taken_white_pieces.push(PieceType::Pawn(Colour::White))
+                            },
+                            PieceType::Bishop(colour) => match colour {
+                                Colour::Black => self.taken_black_pieces.push(PieceType::Bishop(Colour::Black)),
+                                _ => self.taken_white_pieces.push(PieceType::Bishop(Colour::White))
+                            },
+                            PieceType::Knight(colour) => match colour {
+                                Colour::Black => self.taken_black_pieces.push(PieceType::Knight(Colour::Black)),
+                                _ => self.taken_white_pieces.push(PieceType::Knight(Colour::White))
+                            },
+                            PieceType::Rook(colour) => match colour {
+                                Colour::Black => self.taken_black_pieces.push(PieceType::Rook(Colour::Black)),
+                                _ => self.taken_white_pieces.push(PieceType::Rook(Colour::White))
+                            },
+                        }
+                        // a promoted piece goes back to being a pawn once captured
+                        if self.promoted.remove(&(pos_x as u8, pos_y as u8)) {
+                            let tray = if self.board.active_color == Colour::White { &mut self.taken_black_pieces } else { &mut self.taken_white_pieces };
+                            if let Some(taken) = tray.last_mut() {
+                                *taken = PieceType::Pawn(taken.colour());
+                            }
+                        }
+                    }
+                    if self.board.board.contains_key(&Position { file: self.selected_pos.0 as u8, rank: self.selected_pos.1 as u8}) && self.board.board[&Position { file: self.selected_pos.0 as u8, rank: self.selected_pos.1 as u8}].colour() == self.board.active_color {
+                        let (from, to) = ((self.selected_pos.0 as u8, self.selected_pos.1 as u8), (pos_x as u8, pos_y as u8));
+                        let target = self.board.board.get(&Position { file: to.0, rank: to.1 }).copied();
+                        let hooks = self.capture_hooks(from, to);
+                        let sniper = target.is_some() && hooks.sniper;
+                        let moving = self.board.board[&Position { file: self.selected_pos.0 as u8, rank: self.selected_pos.1 as u8 }];
+                        let successful = self.board.make_move(Position { file: self.selected_pos.0 as u8, rank: self.selected_pos.1 as u8 }.to_string(), Position { file: pos_x as u8, rank: pos_y as u8 }.to_string()).is_ok();
+                        if successful {
+                            self.update_castling(self.selected_pos, (pos_x as isize, pos_y as isize));
+                            let to = if sniper { self.selected_pos } else { (pos_x as isize, pos_y as isize) };
+                            self.track_promotion((self.selected_pos.0 as u8, self.selected_pos.1 as u8), (to.0 as u8, to.1 as u8), moving);
+                            self.clear_layers((pos_x as u8, pos_y as u8));
+                            self.move_layers((self.selected_pos.0 as u8, self.selected_pos.1 as u8), (to.0 as u8, to.1 as u8));
+                            // the library moves the rook itself when castling
+                            if moving == PieceType::King(moving.colour()) && (pos_x as isize - self.selected_pos.0).abs() == 2 {
+                                let (rook, rook_to) = if pos_x as isize > self.selected_pos.0 { (8, 6) } else { (1, 4) };
+                                self.move_layers((rook, pos_y as u8), (rook_to, pos_y as u8));
+                            }
+                        }
+                        if sniper {
+                            self.board.board.insert(Position { file: self.selected_pos.0 as u8, rank: self.selected_pos.1 as u8 }, self.board.board[&Position { file: pos_x as u8, rank: pos_y as u8 }]);
+                            self.board.board.remove(&Position { file: pos_x as u8, rank: pos_y as u8 });
+                        }
+                        if successful && self.board.board.contains_key(&Position { file: pos_x as u8, rank: pos_y as u8 }){
+                            match self.board.active_color {
+                                Colour::White => { 
+                                    let p = self.board.board[&Position { file: pos_x as u8, rank: pos_y as u8 }];
+                                    if self.black_mods.contains(&Mods::TripleCheck(p)) && self.board.get_game_state() == GameState::Check {
+                                        if self.board.board.iter_mut().any(|(k, v)| v == &mut PieceType::King(Colour::White) && p.valid_destinations(&Position { file: pos_x as u8, rank: pos_y as u8 }).contains(&k)) {
+                                            self.triple_check_counter = (self.triple_check_counter.0, self.triple_check_counter.1 + 1);
+                                            if self.triple_check_counter.1 >= 3 {
+                                                self.end_game(Some(Colour::Black), Termination::TripleCheck);
+                                            }
+                                        }
+                                    }
+                                },
+                                Colour::Black => { 
+                                    let p = self.board.board[&Position { file: pos_x as u8, rank: pos_y as u8 }];
+                                    if self.white_mods.contains(&Mods::TripleCheck(p)) && self.board.get_game_state() == GameState::Check {
+                                        if self.board.board.iter_mut().any(|(k, v)| v == &mut PieceType::King(Colour::Black) && p.valid_destinations(&Position { file: pos_x as u8, rank: pos_y as u8 }).contains(&k)) {
+                                            self.triple_check_counter = (self.triple_check_counter.0 + 1, self.triple_check_counter.1);
+                                            if self.triple_check_counter.0 >= 3 {
+                                                self.end_game(Some(Colour::White), Termination::TripleCheck);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        if let (true, true, Some(target)) = (successful, taking_move, target) {
+                            self.after_capture(hooks, from, to, target);
+                        }
+                    } else {
+                        if self.selected_pos.1 == 9 { 
+                            self.board.board.insert(Position { file: pos_x as u8, rank: pos_y as u8 }, self.taken_black_pieces[self.selected_pos.0 as usize].type_as_colour(Colour::White));
+                            let dropped = self.taken_black_pieces.remove(self.selected_pos.0 as usize); 
+                            self.spend_spare(Colour::White, dropped);
+                            self.board.get_game_state();
+                            self.board.active_color = Colour::Black;
+                        }
+                        else if self.selected_pos.1 == 10 { 
+                            self.board.board.insert(Position { file: pos_x as u8, rank: pos_y as u8 }, self.taken_white_pieces[self.selected_pos.0 as usize].type_as_colour(Colour::Black));
+                            let dropped = self.taken_white_pieces.remove(self.selected_pos.0 as usize); 
+                            self.spend_spare(Colour::Black, dropped);
+                            self.board.get_game_state();
+                            self.board.active_color = Colour::White;
+                        }
+                    }
+
+                    self.log_move(notation);
+                    self.end_move();
+                    // only drops get here in duck chess
+                    if self.variant == Variant::Duck {
+                        self.duck_pending = true;
+                    }
+                    self.selected_pos = (0, 0);
+                    self.highlighted_pos = Vec::new();
+                    return;
+                }
+                self.highlighted_pos = Vec::new();
+                self.selected_pos = (pos_x as isize, pos_y as isize);
+                if self.variant == Variant::Antichess {
+                    if self.board.board.get(&Position { file: pos_x as u8, rank: pos_y as u8 }).map_or(false, |piece| piece.colour() == self.board.active_color) {
+                        for (file, rank) in self.antichess_moves((pos_x as u8, pos_y as u8)) {
+                            self.highlighted_pos.push((file as isize, rank as isize));
+                        }
+                    }
+                } else if self.variant == Variant::Duck {
+                    for (file, rank) in self.duck_moves((pos_x as u8, pos_y as u8)) {
+                        self.highlighted_pos.push((file as isize, rank as isize));
+                    }
+                } else if self.layered() && !self.atomic_game() && !self.fischer_random() {
+                    if self.board.board.get(&Position { file: pos_x as u8, rank: pos_y as u8 }).map_or(false, |piece| piece.colour() == self.board.active_color) {
+                        let piece = self.board.board[&Position { file: pos_x as u8, rank: pos_y as u8 }];
+                        for (file, rank) in self.layered_legal_moves((pos_x as u8, pos_y as u8)) {
+                            let castling = piece == PieceType::King(piece.colour()) && (file as isize - pos_x as isize).abs() == 2;
+                            if castling && !self.castling_allowed(piece.colour(), file as isize > pos_x as isize) {
+                                continue;
+                            }
+                            self.highlighted_pos.push((file as isize, rank as isize));
+                        }
+                    }
+                } else if self.atomic_game() && !self.fischer_random() {
+                    if self.board.board.get(&Position { file: pos_x as u8, rank: pos_y as u8 }).map_or(false, |piece| piece.colour() == self.board.active_color) {
+                        let piece = self.board.board[&Position { file: pos_x as u8, rank: pos_y as u8 }];
+                        for (file, rank) in self.atomic_moves((pos_x as u8, pos_y as u8)) {
+                            let castling = piece == PieceType::King(piece.colour()) && (file as isize - pos_x as isize).abs() == 2;
+                            if castling && !self.castling_allowed(piece.colour(), file as isize > pos_x as isize) {
+                                continue;
+                            }
+                            self.highlighted_pos.push((file as isize, rank as isize));
+                        }
+                    }
+                } else if self.board.board.contains_key(&Position { file: pos_x as u8, rank: pos_y as u8 }) {
+                    let piece = self.board.board[&Position { file: pos_x as u8, rank: pos_y as u8 }];
+                    for mov in self.board.get_possible_moves(Position { file: pos_x as u8, rank: pos_y as u8 }.to_string()).unwrap() {
+                        let _mov = Position::from_string(mov).unwrap();
+                        // the library doesn't know about castling rights set in the editor or shuffled back ranks
+                        let castling = piece == PieceType::King(piece.colour()) && (_mov.file as isize - pos_x as isize).abs() == 2;
+                        if castling && (self.fischer_random() || !self.castling_allowed(piece.colour(), _mov.file as isize > pos_x as isize)) {
+                            continue;
+                        }
+                        // nobody may give check in racing kings
+                        if self.variant == Variant::RacingKings && self.gives_check((pos_x as u8, pos_y as u8), (_mov.file, _mov.rank)) {
+                            continue;
+                        }
+                        if !self.bounce_legal((pos_x as u8, pos_y as u8), (_mov.file, _mov.rank)) {
+                            continue;
+                        }
+                        self.highlighted_pos.push((_mov.file as isize, _mov.rank as isize));
+                    }
+                    if self.fischer_random() && piece == PieceType::King(self.board.active_color) {
+                        let (_, kingside_rook, queenside_rook) = self.castling_files[if piece.colour() == Colour::White { 0 } else { 1 }];
+                        for (kingside, rook) in [(true, kingside_rook), (false, queenside_rook)].iter() {
+                            if self.can_castle(piece.colour(), *kingside) {
+                                self.highlighted_pos.push((*rook as isize, pos_y as isize));
+                            }
+                        }
+                    }
+                }
+            }
+
+            if x >= SCREEN_SIZE.0 * 0.25 - GRID_CELL_SIZE.0 as f32 && y >= (GRID_CELL_SIZE.1 * 9) as f32 {
+                let pos_x = x - (SCREEN_SIZE.0 * 0.25 - GRID_CELL_SIZE.0 as f32);
+                let pos_x = (pos_x / GRID_CELL_SIZE.0 as f32).ceil();
+                let pos_y = y - ((GRID_CELL_SIZE.1 * 9) as f32);
+                let pos_y = (pos_y / GRID_CELL_SIZE.0 as f32).ceil();
+
+                // drops aren't captures, so antichess forbids them while a capture is available
+                let must_capture = self.variant == Variant::Antichess && self.board.board.iter()
+                    .filter(|(_, piece)| piece.colour() == self.board.active_color)
+                    .any(|(pos, _)| self.antichess_moves((pos.file, pos.rank)).iter().any(|to| self.board.board.contains_key(&Position { file: to.0, rank: to.1 })));
+                if !must_capture && !self.duck_pending && ((pos_y == 1f32 && pos_x <= self.taken_black_pieces.len() as f32) || (pos_y == 2f32 && pos_x <= self.taken_white_pieces.len() as f32)) {
+                    self.selected_pos = ((pos_x - 1f32) as isize, pos_y as isize + 8);
+                    match self.board.active_color {
+                        Colour::Black => {
+                            if self.selected_pos.1 == 10 && (self.bughouse.is_some() || self.black_mods.contains(&Mods::CrazyHouse(self.taken_white_pieces[self.selected_pos.0 as usize])) || self.spare(Colour::Black, self.taken_white_pieces[self.selected_pos.0 as usize])) {
+                                for (x, y) in self.drop_squares(self.taken_white_pieces[self.selected_pos.0 as usize]) {
+                                    self.highlighted_pos.push((x as isize, y as isize));
+                                }
+                            }
+                        },
+                        Colour::White => {
+                            if self.selected_pos.1 == 9 && (self.bughouse.is_some() || self.white_mods.contains(&Mods::CrazyHouse(self.taken_black_pieces[self.selected_pos.0 as usize])) || self.spare(Colour::White, self.taken_black_pieces[self.selected_pos.0 as usize])) {
+                                for (x, y) in self.drop_squares(self.taken_black_pieces[self.selected_pos.0 as usize]) {
+                                    self.highlighted_pos.push((x as isize, y as isize));
+                                }
+                            }
+                        }
+                    }
+                }
+            } 
+
+            // the first half of a double move may not give check, let alone mate
+            if self.double_armed {
+                let checking = self.highlighted_pos.iter().filter(|to| self.first_move_checks(**to)).cloned().collect::<Vec<(isize, isize)>>();
+                self.highlighted_pos.retain(|to| !checking.contains(to));
+            }
+
+            if x >= SCREEN_SIZE.0 * 0.75 && x <= SCREEN_SIZE.0 * 0.75 + (GRID_CELL_SIZE.0 * 4) as f32 && y >= (GRID_CELL_SIZE.1 * 3) as f32 && y < (GRID_CELL_SIZE.1 * 4) as f32 {
+                let pos_x = x - (SCREEN_SIZE.0 * 0.75 as f32);
+                let pos_x = (pos_x / GRID_CELL_SIZE.0 as f32).ceil();
+
+                match pos_x {
+                    1f32 => self.board.set_promotion("queen".to_string()),
+                    2f32 => self.board.set_promotion("rook".to_string()),
+                    3f32 => self.board.set_promotion("bishop".to_string()),
+                    4f32 => self.board.set_promotion("knight".to_string()),
+                    _ => panic!(),
+                };
+            }
+        }
+    }
+}
+
+trait Gets {
+    fn colour(&self) -> Colour;
+    fn type_as_colour(&self, col: Colour) -> PieceType;
+}
+
+impl Gets for PieceType {
+    fn colour(&self) -> Colour {
+        match self {
+            PieceType::Bishop(_colour) => *_colour,
+            PieceType::Rook(_colour) => *_colour,
+            PieceType::Pawn(_colour) => *_colour,
+            PieceType::Knight(_colour) => *_colour,
+            PieceType::Queen(_colour) => *_colour,
+            PieceType::King(_colour) => *_colour,
+        }
+    }
+
+    fn type_as_colour(&self, col: Colour) -> PieceType {
+        match self {
+            PieceType::Bishop(_colour) => PieceType::Bishop(col),
+            PieceType::Rook(_colour) => PieceType::Rook(col),
+            PieceType::Pawn(_colour) => PieceType::Pawn(col),
+            PieceType::Knight(_colour) => PieceType::Knight(col),
+            PieceType::Queen(_colour) => PieceType::Queen(col),
+            PieceType::King(_colour) => PieceType::King(col),
+        }
+    }
+}
+
+/// How a capture plays out once the capturing side's mods have had their say. The hooks always run
+/// in this order, each one seeing what the earlier ones decided:
+///
+/// 1. Shield: a shielded target stops the capture outright, and none of the later hooks run.
+/// 2. Sniper: the capturer takes from afar and stays on its own square.
+/// 3. Atomic: the capture square blows up, taking the capturer along only if it moved onto it
+///    and has no shield of its own to soak up the blast.
+/// 4. Evolution: a capturer that is still standing evolves, if it is its holder's Evolution piece or evolved before.
+/// 5. Extinction, Triple Check and the CrazyHouse trays are then judged on the resulting position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Capture {
+    bounced: bool,
+    sniper: bool,
+    explodes: bool,
+    evolves: bool,
+}
+
+/// Resolves the hooks of a capture by `capturer`, held by a side with `mods`, in the order documented on `Capture`.
+fn resolve_capture(mods: &HashSet<Mods>, capturer: PieceType, target_shielded: bool, capturer_shielded: bool, capturer_evolved: bool) -> Capture {
+    if target_shielded {
+        return Capture { bounced: true, sniper: false, explodes: false, evolves: false };
+    }
+    let sniper = mods.contains(&Mods::Sniper(capturer));
+    let explodes = mods.contains(&Mods::Atomic(capturer));
+    let capturer_survives = !explodes || sniper || capturer_shielded;
+    let evolves = capturer_survives && (capturer_evolved || mods.contains(&Mods::Evolution(capturer))) && evolved(capturer).is_some();
+    Capture { bounced: false, sniper: sniper, explodes: explodes, evolves: evolves }
+}
+
+/// Whether two mods can't sensibly be in play at once, whoever holds them. Mod offers never
+/// conflict with a mod either player already holds.
+fn conflicts(a: &Mods, b: &Mods) -> bool {
+    let variant = |modi: &Mods| match modi { Mods::Antichess | Mods::RacingKings | Mods::Duck => true, _ => false };
+    // mods that change the starting position, which racing kings replaces altogether
+    let setup = |modi: &Mods| match modi {
+        Mods::Chess960 | Mods::Army(_) | Mods::ExtraPawn(_) | Mods::SpareKnight | Mods::Sabotage(_) | Mods::Fairy(_) | Mods::Portal(_) => true,
+        _ => false,
+    };
+    let one_way = |a: &Mods, b: &Mods| match (a, b) {
+        // only one variant can be played in a game
+        _ if variant(a) && variant(b) => a != b,
+        (Mods::RacingKings, other) if setup(other) => true,
+        // nobody may give check in racing kings, and its goal replaces the hill
+        (Mods::RacingKings, Mods::KingOfTheHill) | (Mods::RacingKings, Mods::TripleCheck(_)) | (Mods::RacingKings, Mods::Teleport) => true,
+        // the king isn't royal in antichess
+        (Mods::Antichess, Mods::KingOfTheHill) | (Mods::Antichess, Mods::TripleCheck(_)) | (Mods::Antichess, Mods::Teleport) => true,
+        // duck turns already come in two halves
+        (Mods::Duck, Mods::DoubleMove) => true,
+        // an army replaces the shuffled back rank
+        (Mods::Chess960, Mods::Army(_)) => true,
+        // two armies must fit on the board together, so the Horde can't face another Horde
+        (Mods::Army(a), Mods::Army(b)) => armies()[*a].ranks.len() + armies()[*b].ranks.len() > 8,
+        // portal moves are off in atomic games
+        (Mods::Atomic(_), Mods::Portal(_)) => true,
+        // dropping captured pieces back in would let the last one return forever
+        (Mods::Extinction(piece), Mods::CrazyHouse(other)) => piece.type_as_colour(Colour::White) == other.type_as_colour(Colour::White),
+        // a side that starts without the piece has lost it before the game began
+        (Mods::Extinction(piece), Mods::Sabotage(other)) => piece == other,
+        _ => false,
+    };
+    one_way(a, b) || one_way(b, a)
+}
+
+/// The piece a mod is rolled for, from a roll below 100. Rolls below 90 never give a queen.
+fn roll_piece(roll: u32) -> PieceType {
+    match roll {
+        0..=33 => PieceType::Pawn(Colour::Black),
+        34..=53 => PieceType::Bishop(Colour::Black),
+        54..=73 => PieceType::Knight(Colour::Black),
+        74..=89 => PieceType::Rook(Colour::Black),
+        90..=99 => PieceType::Queen(Colour::Black),
+        _ => PieceType::Pawn(Colour::Black)
+    }
+}
+
+fn generate_mod(col: Colour, rng1: u32, rng2: u32) -> Mods {
+    let piece = roll_piece(rng1 % 100);
+    let mut not_col;
+    if col == Colour::Black {
+        not_col = Colour::White;
+    } else {
+        not_col = Colour::Black;
+    }
+
+    match rng2 % 100 {
+        0..=6 => Mods::KingOfTheHill,
+        7..=13 => Mods::Chess960,
+        14..=20 => Mods::Army(rng1 as usize % armies().len()),
+        21..=24 => Mods::Antichess,
+        25..=27 => Mods::RacingKings,
+        28..=30 => Mods::Duck,
+        31..=40 => Mods::Atomic(piece.type_as_colour(col)),
+        41..=50 => Mods::CrazyHouse(piece.type_as_colour(not_col)),
+        51..=54 => Mods::ExtraPawn((rng1 % 8) as u8 + 1),
+        55..=56 => Mods::SpareKnight,
+        57..=58 => Mods::Sabotage(piece.type_as_colour(not_col)),
+        59..=66 => Mods::Extinction(piece.type_as_colour(not_col)),
+        67..=72 => Mods::Fairy([Fairy::Archbishop, Fairy::Chancellor, Fairy::Amazon][rng1 as usize % 3]),
+        73..=80 => Mods::Sniper(piece.type_as_colour(col)),
+        // a queen has nowhere left to evolve to, so roll among the other pieces at their usual odds
+        81..=85 => Mods::Evolution(roll_piece(rng1 % 90).type_as_colour(col)),
+        86..=88 => Mods::Shield(piece.type_as_colour(col)),
+        89..=90 => Mods::DoubleMove,
+        91 => Mods::Teleport,
+        92..=93 => Mods::TripleCheck(piece.type_as_colour(col)),
+        94..=99 => Mods::Portal(piece.type_as_colour(col)),
+        _ => Mods::KingOfTheHill,
+    }
+}
+
+/// The piece one step up the Evolution ladder, if there is one.
+fn evolved(piece: PieceType) -> Option<PieceType> {
+    let col = piece.colour();
+    match piece {
+        PieceType::Pawn(_) => Some(PieceType::Knight(Colour::White).type_as_colour(col)),
+        PieceType::Knight(_) => Some(PieceType::Bishop(Colour::White).type_as_colour(col)),
+        PieceType::Bishop(_) => Some(PieceType::Rook(Colour::White).type_as_colour(col)),
+        PieceType::Rook(_) => Some(PieceType::Queen(Colour::White).type_as_colour(col)),
+        _ => None,
+    }
+}
+
+fn piece_name(piece: PieceType) -> &'static str {
+    match piece {
+        PieceType::King(_) => "King",
+        PieceType::Queen(_) => "Queen",
+        PieceType::Rook(_) => "Rook",
+        PieceType::Bishop(_) => "Bishop",
+        PieceType::Knight(_) => "Knight",
+        PieceType::Pawn(_) => "Pawn",
+    }
+}
+
+/// One example of every mod, in the order they are listed on the help screen.
+fn help_examples() -> Vec<Mods> {
+    vec![
+        Mods::CrazyHouse(PieceType::Knight(Colour::Black)),
+        Mods::Atomic(PieceType::Knight(Colour::White)),
+        Mods::Sniper(PieceType::Rook(Colour::White)),
+        Mods::KingOfTheHill,
+        Mods::Extinction(PieceType::Knight(Colour::Black)),
+        Mods::TripleCheck(PieceType::Bishop(Colour::White)),
+        Mods::Chess960,
+        Mods::Army(0),
+        Mods::Antichess,
+        Mods::RacingKings,
+        Mods::Duck,
+        Mods::Portal(PieceType::Bishop(Colour::White)),
+        Mods::Evolution(PieceType::Pawn(Colour::White)),
+        Mods::Shield(PieceType::Knight(Colour::White)),
+        Mods::DoubleMove,
+        Mods::Fairy(Fairy::Archbishop),
+        Mods::ExtraPawn(5),
+        Mods::SpareKnight,
+        Mods::Sabotage(PieceType::Queen(Colour::Black)),
+        Mods::Teleport,
+    ]
+}
+
+/// Squares cleared by an atomic capture on `at`: the capture square if `with_capturer`, and every
+/// piece other than pawns on the squares around it.
+fn blast_squares(board: &HashMap<Position, PieceType>, at: (u8, u8), with_capturer: bool) -> Vec<(u8, u8)> {
+    let mut squares = Vec::new();
+    for file in at.0.saturating_sub(1).max(1)..=(at.0 + 1).min(8) {
+        for rank in at.1.saturating_sub(1).max(1)..=(at.1 + 1).min(8) {
+            let centre = (file, rank) == at;
+            match board.get(&Position { file: file, rank: rank }) {
+                Some(_) if centre && with_capturer => squares.push((file, rank)),
+                Some(piece) if !centre && *piece != PieceType::Pawn(piece.colour()) => squares.push((file, rank)),
+                _ => (),
+            }
+        }
+    }
+    squares
+}
+
+/// Where the partner board is drawn in bughouse, right of the main board.
+fn partner_board_rect() -> graphics::Rect {
+    graphics::Rect::new(SCREEN_SIZE.0 * 0.25 + (GRID_CELL_SIZE.0 * 8) as f32, 0.0, MINI_CELL_SIZE * 8.0, MINI_CELL_SIZE * 8.0)
+}
+
+/// Clock time as minutes and seconds.
+fn clock_string(seconds: f32) -> String {
+    let seconds = seconds.max(0.0).ceil() as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Every mod that can be rolled for `col`.
+fn all_mods(col: Colour) -> Vec<Mods> {
+    let not_col = opposite(col);
+    let pieces = [PieceType::Pawn(col), PieceType::Knight(col), PieceType::Bishop(col), PieceType::Rook(col), PieceType::Queen(col)];
+    let mut mods = vec![Mods::KingOfTheHill, Mods::Chess960, Mods::Antichess, Mods::RacingKings, Mods::Duck, Mods::DoubleMove, Mods::SpareKnight, Mods::Teleport];
+    for file in 1..9 {
+        mods.push(Mods::ExtraPawn(file));
+    }
+    for army in 0..armies().len() {
+        mods.push(Mods::Army(army));
+    }
+    for fairy in [Fairy::Archbishop, Fairy::Chancellor, Fairy::Amazon].iter() {
+        mods.push(Mods::Fairy(*fairy));
+    }
+    for piece in pieces.iter() {
+        mods.push(Mods::CrazyHouse(piece.type_as_colour(not_col)));
+        mods.push(Mods::Atomic(*piece));
+        mods.push(Mods::Portal(*piece));
+        if evolved(*piece).is_some() {
+            mods.push(Mods::Evolution(*piece));
+        }
+        mods.push(Mods::Sniper(*piece));
+        mods.push(Mods::Shield(*piece));
+        mods.push(Mods::Extinction(piece.type_as_colour(not_col)));
+        mods.push(Mods::Sabotage(piece.type_as_colour(not_col)));
+        mods.push(Mods::TripleCheck(*piece));
+    }
+    mods
+}
+
+/// Seconds since the Unix epoch.
+fn unix_time() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+/// The date as YYYY-MM-DD, `days` days after 1970-01-01.
+fn civil_date(days: u64) -> String {
+    // count in 400 year eras of the proleptic Gregorian calendar, with years starting in March
+    let days = days as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Every game in the history file, skipping any line that can't be read.
+fn load_history() -> Vec<GameRecord> {
+    std::fs::read_to_string(HISTORY_FILE)
+        .map(|text| text.lines().filter_map(GameRecord::parse).collect())
+        .unwrap_or_default()
+}
+
+/// Adds a finished game to the end of the history file.
+fn append_history(record: &GameRecord) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(HISTORY_FILE)?;
+    writeln!(file, "{}", record.to_line())
+}
+
+/// Every profile in the profiles file, skipping any line that can't be read.
+fn load_profiles() -> Vec<Profile> {
+    std::fs::read_to_string(PROFILES_FILE)
+        .map(|text| text.lines().filter_map(Profile::parse).collect())
+        .unwrap_or_default()
+}
+
+/// Score a player rated `rating` is expected to get against one rated `opponent`, from 0 to 1.
+fn expected_score(rating: f32, opponent: f32) -> f32 {
+    1.0 / (1.0 + 10f32.powf((opponent - rating) / 400.0))
+}
+
+/// Wins and games of every mod in the history, counted for whoever held it, best win rate first.
+fn mod_win_rates(history: &[GameRecord]) -> Vec<(String, u32, u32)> {
+    let mut results: HashMap<String, (u32, u32)> = HashMap::new();
+    for record in history.iter() {
+        for (col, mods) in [Colour::White, Colour::Black].iter().zip(record.mods.iter()) {
+            for name in mods.iter() {
+                let result = results.entry(name.clone()).or_insert((0, 0));
+                if record.winner == Some(*col) {
+                    result.0 += 1;
+                }
+                result.1 += 1;
+            }
+        }
+    }
+    let mut results = results.into_iter().map(|(name, (wins, games))| (name, wins, games)).collect::<Vec<(String, u32, u32)>>();
+    results.sort_by(|a, b| (b.1 * a.2).cmp(&(a.1 * b.2)).then(b.2.cmp(&a.2)).then(a.0.cmp(&b.0)));
+    results
+}
+
+/// The games of the series with the most games in it.
+fn longest_series(history: &[GameRecord]) -> Vec<GameRecord> {
+    let mut series: Vec<u64> = history.iter().map(|record| record.series).collect();
+    series.sort();
+    series.dedup();
+    series.into_iter()
+        .map(|start| history.iter().filter(|record| record.series == start).cloned().collect::<Vec<GameRecord>>())
+        .max_by_key(|games| games.len())
+        .unwrap_or_default()
+}
+
+/// Wins of each player against the other, and the draws between them, for every pair of names that met.
+fn head_to_head(history: &[GameRecord]) -> Vec<(String, String, u32, u32, u32)> {
+    let mut meetings: Vec<(String, String, u32, u32, u32)> = Vec::new();
+    for record in history.iter() {
+        let (first, second) = if record.players[0] <= record.players[1] { (0, 1) } else { (1, 0) };
+        let index = match meetings.iter().position(|meeting| meeting.0 == record.players[first] && meeting.1 == record.players[second]) {
+            Some(index) => index,
+            None => {
+                meetings.push((record.players[first].clone(), record.players[second].clone(), 0, 0, 0));
+                meetings.len() - 1
+            },
+        };
+        let meeting = &mut meetings[index];
+        match record.winner {
+            Some(Colour::White) if first == 0 => meeting.2 += 1,
+            Some(Colour::Black) if first == 1 => meeting.2 += 1,
+            Some(_) => meeting.3 += 1,
+            None => meeting.4 += 1,
+        }
+    }
+    meetings.sort();
+    meetings
+}
+
+/// Army templates for the Army mod, in the order they appear in `resources/armies.txt`.
+fn armies() -> &'static [ArmyTemplate] {
+    static ARMIES: std::sync::OnceLock<Vec<ArmyTemplate>> = std::sync::OnceLock::new();
+    ARMIES.get_or_init(parse_armies)
+}
+
+fn parse_armies() -> Vec<ArmyTemplate> {
+    let mut armies: Vec<ArmyTemplate> = Vec::new();
+    for line in include_str!("../resources/armies.txt").lines().map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            armies.push(ArmyTemplate {
+                name: line[1..line.len() - 1].to_string(),
+                description: String::new(),
+                wipeout: false,
+                ranks: Vec::new(),
+            });
+            continue;
+        }
+        let army = armies.last_mut().expect("army data must start with a [Name] header");
+        if line == "wipeout" && army.description.is_empty() {
+            army.wipeout = true;
+        } else if army.description.is_empty() {
+            army.description = line.to_string();
+        } else {
+            let mut rank = Vec::new();
+            for c in line.chars() {
+                match c.to_digit(10) {
+                    Some(empty) => rank.extend((0..empty).map(|_| None)),
+                    None => rank.push(piece_from_char(c)),
+                }
+            }
+            rank.resize(8, None);
+            army.ranks.push(rank);
+        }
+    }
+    armies
+}
+
+/// Pieces offered by the board editor, white on the first row and black on the second.
+fn editor_palette() -> Vec<PieceType> {
+    let mut palette = Vec::new();
+    for col in [Colour::White, Colour::Black].iter() {
+        palette.push(PieceType::King(*col));
+        palette.push(PieceType::Queen(*col));
+        palette.push(PieceType::Rook(*col));
+        palette.push(PieceType::Bishop(*col));
+        palette.push(PieceType::Knight(*col));
+        palette.push(PieceType::Pawn(*col));
+    }
+    palette
+}
+
+/// Tutorial lessons, one per mod in the same order as `help_examples`.
+fn lessons() -> Vec<Lesson> {
+    vec![
+        Lesson {
+            modi: Mods::CrazyHouse(PieceType::Knight(Colour::Black)),
+            tray: Some(PieceType::Knight(Colour::Black)),
+            prompt: "You captured a knight earlier. Click it in your tray below the board, then drop it on f6.",
+            from: (0, 9),
+            to: (6, 6),
+            outcome: "The knight joined your army and now checks the black king from f6.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Atomic(PieceType::Knight(Colour::White)),
+            position: "7k/8/2p1b3/3r4/4q3/2N5/8/6K1",
+            prompt: "Capture the rook on d5 with your knight on c3.",
+            from: (3, 3),
+            to: (4, 5),
+            outcome: "Boom! The rook, bishop, queen and your own knight are gone, but the pawn on c6 survived the blast.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Sniper(PieceType::Rook(Colour::White)),
+            position: "4k3/n7/8/8/8/8/8/R3K3",
+            prompt: "Capture the knight on a7 with your rook on a1.",
+            from: (1, 1),
+            to: (1, 7),
+            outcome: "The knight is gone, yet your rook never left a1.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::KingOfTheHill,
+            position: "4k3/8/8/8/8/4K3/8/8",
+            prompt: "Step your king from e3 onto e4.",
+            from: (5, 3),
+            to: (5, 4),
+            outcome: "Your king stands on the hill. In a real game that wins on the spot.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Extinction(PieceType::Knight(Colour::Black)),
+            position: "4k3/8/8/3n4/8/8/6B1/4K3",
+            prompt: "Black has a single knight left. Take it on d5 with your bishop.",
+            from: (7, 2),
+            to: (4, 5),
+            outcome: "Black's knights are extinct, so you win even though the king is safe.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::TripleCheck(PieceType::Bishop(Colour::White)),
+            position: "4k3/8/8/8/8/8/8/4KB2",
+            triple_checks: 2,
+            prompt: "Your bishop has already given check twice. Check the king once more from b5.",
+            from: (6, 1),
+            to: (2, 5),
+            outcome: "That was the third bishop check, which wins the game.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Chess960,
+            position: "4k3/8/8/8/8/8/8/RK4R1",
+            prompt: "In Chess960 you castle by moving your king onto its rook. Click the king on b1, then the rook on g1.",
+            from: (2, 1),
+            to: (7, 1),
+            outcome: "The king and rook ended up on g1 and f1, just like after castling kingside in normal chess.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Army(0),
+            position: "4k3/8/2r1n3/8/3P4/8/PPP2PPP/4K3",
+            prompt: "The Horde has nothing but pawns in front of its king. Push the pawn on d4 to d5.",
+            from: (4, 4),
+            to: (4, 5),
+            outcome: "Your pawn forks the rook and the knight. Black wins against the Horde only by capturing every pawn.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Antichess,
+            position: "4k3/8/8/3p4/4P3/8/8/4K3",
+            prompt: "Captures are compulsory in antichess, so your only move is to take the pawn on d5 with e4.",
+            from: (5, 4),
+            to: (4, 5),
+            outcome: "Whoever loses all their pieces first wins, so being forced to take can be a trap.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::RacingKings,
+            position: "8/6K1/8/8/8/8/8/7k",
+            prompt: "The first king to reach the eighth rank wins. Step your king from g7 to g8.",
+            from: (7, 7),
+            to: (7, 8),
+            outcome: "Black's king can't reach the eighth rank in reply, so the race is yours. Giving check is never allowed.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Duck,
+            position: "4k3/8/8/8/Q7/8/8/4K3",
+            duck: Some((4, 4)),
+            prompt: "There is no check in duck chess, and Black left the king hanging. Take it with your queen on a4.",
+            from: (1, 4),
+            to: (5, 8),
+            outcome: "Taking the king wins. After every other move you would also have to put the duck on a new empty square.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Portal(PieceType::Bishop(Colour::White)),
+            position: "7k/4r3/8/8/8/8/8/2B3K1",
+            portals: Some(((4, 2), (4, 6))),
+            prompt: "Your bishop can travel through the portals on d2 and d6. Take the rook on e7 with it.",
+            from: (3, 1),
+            to: (5, 7),
+            outcome: "The bishop went into d2, came out of d6 and carried on to e7.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Evolution(PieceType::Pawn(Colour::White)),
+            position: "4k3/8/8/3n4/4P3/8/8/4K3",
+            prompt: "Take the knight on d5 with your pawn on e4.",
+            from: (5, 4),
+            to: (4, 5),
+            outcome: "Your pawn evolved into a knight as it captured.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Shield(PieceType::Knight(Colour::White)),
+            position: "4k3/8/4p3/3p4/8/2N5/8/4K3",
+            prompt: "Your knights carry shields. Take the pawn on d5 with your knight on c3, even though e6 defends it.",
+            from: (3, 3),
+            to: (4, 5),
+            outcome: "If Black takes back, the shield breaks instead of your knight.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::DoubleMove,
+            position: "4k3/8/8/8/8/8/4P3/4K3",
+            prompt: "Your double move is already called, as the mod panel shows. Push your pawn from e2 to e4.",
+            from: (5, 2),
+            to: (5, 4),
+            outcome: "It is still your move. In a game you call the double move with M, once per game.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Fairy(Fairy::Archbishop),
+            position: "4k3/8/8/8/8/8/4r3/2B3K1",
+            prompt: "Your bishop on c1 is an archbishop, which can also jump like a knight. Take the rook on e2.",
+            from: (3, 1),
+            to: (5, 2),
+            outcome: "A bishop could never have reached e2 from c1, but the archbishop's knight jump can.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::ExtraPawn(5),
+            position: "4k3/8/8/8/3p4/4P3/4P3/4K3",
+            prompt: "You start with an extra pawn on e3. Take the pawn on d4 with it.",
+            from: (5, 3),
+            to: (4, 4),
+            outcome: "Your e2 pawn is still at home to back up the centre.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::SpareKnight,
+            tray: Some(PieceType::Knight(Colour::Black)),
+            prompt: "You start with a spare knight in your tray. Click it below the board, then drop it on f6.",
+            from: (0, 9),
+            to: (6, 6),
+            outcome: "The spare knight can go on any empty square, whenever you like.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Sabotage(PieceType::Queen(Colour::Black)),
+            position: "4k3/8/8/8/8/8/8/3QK3",
+            prompt: "Black's queen was taken off the board before the game began. Check the king from a4 with yours.",
+            from: (4, 1),
+            to: (1, 4),
+            outcome: "With no queen of its own, Black has nothing to trade for yours.",
+            ..LESSON
+        },
+        Lesson {
+            modi: Mods::Teleport,
+            position: "4k3/8/8/1N6/8/8/8/r3K3",
+            prompt: "Your king is in check. Right-click it, then click the knight on b5 to swap places with it.",
+            from: (5, 1),
+            to: (2, 5),
+            outcome: "The king escaped to b5 and the knight took its place. You get one teleport per game.",
+            ..LESSON
+        },
+    ]
+}
+
+fn piece_char(piece: PieceType) -> char {
+    let c = match piece {
+        PieceType::King(_) => 'k',
+        PieceType::Queen(_) => 'q',
+        PieceType::Rook(_) => 'r',
+        PieceType::Bishop(_) => 'b',
+        PieceType::Knight(_) => 'n',
+        PieceType::Pawn(_) => 'p',
+    };
+    if piece.colour() == Colour::White { c.to_ascii_uppercase() } else { c }
+}
+
+fn piece_from_char(c: char) -> Option<PieceType> {
+    let col = if c.is_ascii_uppercase() { Colour::White } else { Colour::Black };
+    match c.to_ascii_lowercase() {
+        'k' => Some(PieceType::King(col)),
+        'q' => Some(PieceType::Queen(col)),
+        'r' => Some(PieceType::Rook(col)),
+        'b' => Some(PieceType::Bishop(col)),
+        'n' => Some(PieceType::Knight(col)),
+        'p' => Some(PieceType::Pawn(col)),
+        _ => None,
+    }
+}
+
+/// The piece placement field of a FEN string for `board`, writing the pieces in `fairies` by their own letters.
+fn fen_placement(board: &HashMap<Position, PieceType>, fairies: &HashMap<(u8, u8), Fairy>) -> String {
+    let mut placement = String::new();
+    for rank in (1..9).rev() {
+        let mut empty = 0;
+        for file in 1..9 {
+            match board.get(&Position { file: file, rank: rank }) {
+                Some(piece) => {
+                    if empty > 0 {
+                        placement.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    placement.push(match fairies.get(&(file, rank)) {
+                        Some(fairy) if piece.colour() == Colour::White => fairy.letter(),
+                        Some(fairy) => fairy.letter().to_ascii_lowercase(),
+                        None => piece_char(*piece),
+                    });
+                },
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            placement.push_str(&empty.to_string());
+        }
+        if rank > 1 {
+            placement.push('/');
+        }
+    }
+    placement
+}
+
+/// Builds a game from the piece placement field of a FEN string, e.g. "4k3/8/8/8/8/8/8/4K3".
+fn load_position(placement: &str, to_move: Colour) -> Game {
+    let mut game = Game::new();
+    game.board.clear();
+    for (i, row) in placement.split('/').enumerate() {
+        let rank = 8 - i as u8;
+        let mut file = 1;
+        for c in row.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                file += empty as u8;
+                continue;
+            }
+            if let Some(piece) = piece_from_char(c) {
+                game.board.insert(Position { file: file, rank: rank }, piece);
+            }
+            file += 1;
+        }
+    }
+    game.active_color = to_move;
+    game
+}
+
+/// Back rank of the Chess960 setup with the given Scharnagl number, from the a-file to the h-file.
+fn chess960_back_rank(n: u32, col: Colour) -> Vec<PieceType> {
+    let mut rank: Vec<Option<PieceType>> = vec![None; 8];
+    let mut n = n % 960;
+
+    // bishops go on opposite colours, then the queen and knights on the free squares
+    rank[(n % 4 * 2 + 1) as usize] = Some(PieceType::Bishop(col));
+    n /= 4;
+    rank[(n % 4 * 2) as usize] = Some(PieceType::Bishop(col));
+    n /= 4;
+    let free = |rank: &Vec<Option<PieceType>>| (0..8).filter(|i| rank[*i].is_none()).collect::<Vec<usize>>();
+    let queen = free(&rank)[(n % 6) as usize];
+    rank[queen] = Some(PieceType::Queen(col));
+    n /= 6;
+    let knights = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)][n as usize];
+    let squares = free(&rank);
+    rank[squares[knights.0]] = Some(PieceType::Knight(col));
+    rank[squares[knights.1]] = Some(PieceType::Knight(col));
+
+    // the king always ends up between the rooks
+    let squares = free(&rank);
+    rank[squares[0]] = Some(PieceType::Rook(col));
+    rank[squares[1]] = Some(PieceType::King(col));
+    rank[squares[2]] = Some(PieceType::Rook(col));
+    rank.into_iter().map(|piece| piece.unwrap()).collect()
+}
+
+/// Squares the piece on the given square could move to, ignoring checks, castling and en passant.
+fn piece_moves(board: &HashMap<Position, PieceType>, file: u8, rank: u8) -> Vec<(u8, u8)> {
+    let piece = match board.get(&Position { file: file, rank: rank }) {
+        Some(piece) => *piece,
+        None => return Vec::new(),
+    };
+    let col = piece.colour();
+    let on_board = |file: i8, rank: i8| file >= 1 && file <= 8 && rank >= 1 && rank <= 8;
+    let at = |file: i8, rank: i8| board.get(&Position { file: file as u8, rank: rank as u8 });
+    let (file, rank) = (file as i8, rank as i8);
+    let mut moves = Vec::new();
+
+    let steps: Vec<(i8, i8)> = match piece {
+        PieceType::Pawn(_) => {
+            let forward = if col == Colour::White { 1 } else { -1 };
+            let start = if col == Colour::White { 2 } else { 7 };
+            if on_board(file, rank + forward) && at(file, rank + forward).is_none() {
+                moves.push((file as u8, (rank + forward) as u8));
+                if rank == start && at(file, rank + forward * 2).is_none() {
+                    moves.push((file as u8, (rank + forward * 2) as u8));
+                }
+            }
+            for side in [-1, 1].iter() {
+                if on_board(file + side, rank + forward) && at(file + side, rank + forward).map_or(false, |other| other.colour() != col) {
+                    moves.push(((file + side) as u8, (rank + forward) as u8));
+                }
+            }
+            return moves;
+        },
+        PieceType::Knight(_) => vec![(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)],
+        _ => vec![(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)],
+    };
+    let sliding = match piece {
+        PieceType::Rook(_) | PieceType::Bishop(_) | PieceType::Queen(_) => true,
+        _ => false,
+    };
+    for (i, (df, dr)) in steps.iter().enumerate() {
+        if (piece == PieceType::Rook(col) && i >= 4) || (piece == PieceType::Bishop(col) && i < 4) {
+            continue;
+        }
+        let mut distance = 1;
+        while on_board(file + df * distance, rank + dr * distance) {
+            let target = ((file + df * distance) as u8, (rank + dr * distance) as u8);
+            match at(file + df * distance, rank + dr * distance) {
+                Some(other) if other.colour() == col => break,
+                Some(_) => {
+                    moves.push(target);
+                    break;
+                },
+                None => moves.push(target),
+            }
+            if !sliding {
+                break;
+            }
+            distance += 1;
+        }
+    }
+    moves
+}
+
+/// Whether any piece of colour `by` attacks the given square.
+fn square_attacked(board: &HashMap<Position, PieceType>, file: u8, rank: u8, by: Colour) -> bool {
+    let at = |file: i8, rank: i8| -> Option<PieceType> {
+        if file < 1 || file > 8 || rank < 1 || rank > 8 {
+            return None;
+        }
+        board.get(&Position { file: file as u8, rank: rank as u8 }).copied()
+    };
+    let (file, rank) = (file as i8, rank as i8);
+
+    // pawns attack diagonally forwards, so look one rank back from their point of view
+    let behind = if by == Colour::White { -1 } else { 1 };
+    if at(file - 1, rank + behind) == Some(PieceType::Pawn(by)) || at(file + 1, rank + behind) == Some(PieceType::Pawn(by)) {
+        return true;
+    }
+
+    let knight_jumps = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+    if knight_jumps.iter().any(|(df, dr)| at(file + df, rank + dr) == Some(PieceType::Knight(by))) {
+        return true;
+    }
+
+    let directions = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+    for (i, (df, dr)) in directions.iter().enumerate() {
+        let slider = if i < 4 { PieceType::Rook(by) } else { PieceType::Bishop(by) };
+        let mut distance = 1;
+        while file + df * distance >= 1 && file + df * distance <= 8 && rank + dr * distance >= 1 && rank + dr * distance <= 8 {
+            match at(file + df * distance, rank + dr * distance) {
+                Some(piece) if piece == slider || piece == PieceType::Queen(by) => return true,
+                Some(piece) if distance == 1 && piece == PieceType::King(by) => return true,
+                Some(_) => break,
+                None => distance += 1,
+            }
+        }
+    }
+    false
+}
+
+/// Screen area of a board square, or of a tray slot when the rank is 9 or 10.
+fn square_rect(pos: (isize, isize)) -> graphics::Rect {
+    if pos.1 > 8 {
+        graphics::Rect::new(SCREEN_SIZE.0 * 0.25 - GRID_CELL_SIZE.0 as f32 + (pos.0 * GRID_CELL_SIZE.0 as isize) as f32,
+                            (pos.1 * GRID_CELL_SIZE.1 as isize) as f32,
+                            GRID_CELL_SIZE.0 as f32,
+                            GRID_CELL_SIZE.1 as f32)
+    } else {
+        graphics::Rect::new(SCREEN_SIZE.0 * 0.25 + ((pos.0 - 1) * GRID_CELL_SIZE.0 as isize) as f32,
+                            ((8 - pos.1) * GRID_CELL_SIZE.1 as isize) as f32,
+                            GRID_CELL_SIZE.0 as f32,
+                            GRID_CELL_SIZE.1 as f32)
+    }
+}
+
+/// Board square under the given screen coordinates, if any.
+fn board_square_at(x: f32, y: f32) -> Option<(u8, u8)> {
+    let file = ((x - SCREEN_SIZE.0 * 0.25) / GRID_CELL_SIZE.0 as f32).floor() as i32 + 1;
+    let rank = 8 - (y / GRID_CELL_SIZE.1 as f32).floor() as i32;
+    if file >= 1 && file <= 8 && rank >= 1 && rank <= 8 {
+        Some((file as u8, rank as u8))
+    } else {
+        None
+    }
+}
+
+fn opposite(col: Colour) -> Colour {
+    if col == Colour::White { Colour::Black } else { Colour::White }
+}
+
+/// Area of the `i`th mod offer on the mod screen.
+fn mod_offer_rect(i: usize) -> graphics::Rect {
+    graphics::Rect::new(GRID_CELL_SIZE.0 as f32 * 0.5,
+                        GRID_CELL_SIZE.1 as f32 * 0.5 + (MOD_BOX_HEIGHT + GRID_CELL_SIZE.1 as f32 * 0.5) * i as f32,
+                        SCREEN_SIZE.0 - GRID_CELL_SIZE.0 as f32,
+                        MOD_BOX_HEIGHT)
+}
+
+/// Banish button inside the `i`th mod offer.
+fn mod_banish_rect(i: usize) -> graphics::Rect {
+    let offer = mod_offer_rect(i);
+    graphics::Rect::new(offer.x + offer.w - GRID_CELL_SIZE.0 as f32 * 3.5,
+                        offer.y + (offer.h - GRID_CELL_SIZE.1 as f32) * 0.5,
+                        GRID_CELL_SIZE.0 as f32 * 3f32,
+                        GRID_CELL_SIZE.1 as f32)
+}
+
+/// Area in the right-hand panel, measured in grid cells from its top left corner.
+fn panel_rect(column: f32, row: f32, width: f32) -> graphics::Rect {
+    graphics::Rect::new(SCREEN_SIZE.0 * 0.75 + GRID_CELL_SIZE.0 as f32 * column,
+                        GRID_CELL_SIZE.1 as f32 * row,
+                        GRID_CELL_SIZE.0 as f32 * width,
+                        GRID_CELL_SIZE.1 as f32)
+}
+
+/// Row of the `i`th profile on the profile screen.
+fn profile_row_rect(i: usize) -> graphics::Rect {
+    graphics::Rect::new(GRID_CELL_SIZE.0 as f32 * 0.5,
+                        GRID_CELL_SIZE.1 as f32 * 2.5 + i as f32 * 30f32,
+                        SCREEN_SIZE.0 - GRID_CELL_SIZE.0 as f32,
+                        26f32)
+}
+
+/// Box on a profile row that cycles its piece set.
+fn profile_set_rect(i: usize) -> graphics::Rect {
+    let row = profile_row_rect(i);
+    graphics::Rect::new(SCREEN_SIZE.0 * 0.55, row.y, SCREEN_SIZE.0 * 0.18, row.h)
+}
+
+/// Box on a profile row that switches the side it prefers.
+fn profile_side_rect(i: usize) -> graphics::Rect {
+    let row = profile_row_rect(i);
+    graphics::Rect::new(SCREEN_SIZE.0 * 0.75, row.y, SCREEN_SIZE.0 * 0.18, row.h)
+}
+
+/// Reroll button below the mod offers.
+fn mod_reroll_rect() -> graphics::Rect {
+    graphics::Rect::new(GRID_CELL_SIZE.0 as f32 * 0.5,
+                        SCREEN_SIZE.1 - GRID_CELL_SIZE.1 as f32 * 1.5,
+                        GRID_CELL_SIZE.0 as f32 * 4f32,
+                        GRID_CELL_SIZE.1 as f32)
+}
+
+/// Skip button next to the reroll button.
+fn mod_skip_rect() -> graphics::Rect {
+    let reroll = mod_reroll_rect();
+    graphics::Rect::new(reroll.x + reroll.w + GRID_CELL_SIZE.0 as f32 * 0.5, reroll.y, reroll.w, reroll.h)
+}
+
+fn int_to_pos_tuple(x: isize) -> (isize, isize) {
+    let pos_x = &x % 8;
+    let pos_y = ((x as f32 / 8.0).trunc()) as isize; 
+    (pos_x + 1, 9 - (pos_y + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every mod either player can hold, each of them once.
+    fn every_mod() -> Vec<Mods> {
+        let mut mods = all_mods(Colour::White);
+        for modi in all_mods(Colour::Black) {
+            if !mods.contains(&modi) {
+                mods.push(modi);
+            }
+        }
+        mods
+    }
+
+    #[test]
+    fn generated_mods_are_all_known() {
+        for col in [Colour::White, Colour::Black].iter() {
+            let known = all_mods(*col);
+            for rng1 in 0..100 {
+                for rng2 in 0..100 {
+                    let modi = generate_mod(*col, rng1, rng2);
+                    assert!(known.contains(&modi), "{:?} is missing from all_mods", modi);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn evolution_rolls_keep_the_piece_odds() {
+        let evolved = (0..9000).map(|rng1| generate_mod(Colour::White, rng1, 81)).collect::<Vec<Mods>>();
+        let pawns = evolved.iter().filter(|modi| **modi == Mods::Evolution(PieceType::Pawn(Colour::White))).count();
+        assert!(!evolved.contains(&Mods::Evolution(PieceType::Queen(Colour::White))));
+        // pawns are 34 of the 90 rolls left once queens are out
+        assert_eq!(pawns * 90, evolved.len() * 34);
+    }
+
+    #[test]
+    fn conflicts_are_symmetric() {
+        let mods = every_mod();
+        for a in mods.iter() {
+            // an army too big to face itself is the only mod both sides can't hold at once
+            assert!(!conflicts(a, a) || matches!(a, Mods::Army(_)), "{:?} conflicts with itself", a);
+            for b in mods.iter() {
+                assert_eq!(conflicts(a, b), conflicts(b, a), "{:?} and {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn captures_play_out_in_hook_order() {
+        let knight = PieceType::Knight(Colour::White);
+        let at = |state: &AppState, square: (u8, u8)| state.board.board.get(&Position { file: square.0, rank: square.1 }).copied();
+        // the knight on c3 takes the pawn on d5, next to the rook on c6
+        let cases: [(&[Mods], &[(u8, u8)], Option<PieceType>, Option<PieceType>, Option<PieceType>, Option<PieceType>); 6] = [
+            // a shielded target stops even an atomic capture: nothing moves and nothing explodes
+            (&[Mods::Atomic(knight)], &[(4, 5)], Some(knight), Some(PieceType::Pawn(Colour::Black)), Some(PieceType::Rook(Colour::Black)), None),
+            // a sniper stays behind
+            (&[Mods::Sniper(knight)], &[], Some(knight), None, Some(PieceType::Rook(Colour::Black)), None),
+            // an atomic capturer goes up with everything around it
+            (&[Mods::Atomic(knight)], &[], None, None, None, None),
+            // unless it snipes from afar
+            (&[Mods::Atomic(knight), Mods::Sniper(knight)], &[], Some(knight), None, None, None),
+            // or its shield soaks up the blast, so it lives on to evolve
+            (&[Mods::Atomic(knight), Mods::Evolution(knight)], &[(3, 3)], None, Some(PieceType::Bishop(Colour::White)), None, Some(PieceType::Bishop(Colour::White))),
+            // and a plain capturer just evolves
+            (&[Mods::Evolution(knight)], &[], None, Some(PieceType::Bishop(Colour::White)), Some(PieceType::Rook(Colour::Black)), Some(PieceType::Bishop(Colour::White))),
+        ];
+        for (mods, shields, c3, d5, c6, evolved_piece) in cases.iter() {
+            let mut state = AppState::headless();
+            state.board = load_position("4k3/8/2r5/3p4/8/2N5/8/4K3", Colour::White);
+            state.white_mods = mods.iter().copied().collect();
+            state.shields = shields.iter().copied().collect();
+            click_square(&mut state, MouseButton::Left, (3, 3));
+            click_square(&mut state, MouseButton::Left, (4, 5));
+            let context = format!("{:?} with shields on {:?}", mods, shields);
+            assert_eq!(at(&state, (3, 3)), *c3, "{}", context);
+            assert_eq!(at(&state, (4, 5)), *d5, "{}", context);
+            assert_eq!(at(&state, (3, 6)), *c6, "{}", context);
+            assert_eq!(state.evolving.contains(&(4, 5)), evolved_piece.is_some(), "{}", context);
+            assert!(state.shields.is_empty(), "{}", context);
+            assert_eq!(state.board.active_color, Colour::Black, "{}", context);
+        }
+    }
+
+    /// Clicks the middle of a board or tray square.
+    fn click_square(state: &mut AppState, button: MouseButton, pos: (isize, isize)) {
+        let rect = square_rect(pos);
+        state.click(button, rect.x + rect.w * 0.5, rect.y + rect.h * 0.5);
+    }
+
+    #[test]
+    fn every_mod_has_a_playable_lesson() {
+        for modi in help_examples() {
+            assert!(lessons().iter().any(|lesson| lesson.modi == modi), "no lesson for {:?}", modi);
+        }
+        let mut state = AppState::headless();
+        state.move_log.push("e4".to_string());
+        for (i, lesson) in lessons().iter().enumerate() {
+            state.start_tutorial(i);
+            if lesson.modi == Mods::Teleport {
+                click_square(&mut state, MouseButton::Right, lesson.from);
+            } else {
+                click_square(&mut state, MouseButton::Left, lesson.from);
+            }
+            click_square(&mut state, MouseButton::Left, lesson.to);
+            assert!(state.tutorial.as_ref().unwrap().done, "lesson for {:?} didn't play", lesson.modi);
+        }
+        state.end_tutorial();
+        assert_eq!(state.move_log, vec!["e4".to_string()]);
+    }
+
+    #[test]
+    fn board_clicks_stay_on_the_board() {
+        let left = SCREEN_SIZE.0 * 0.25;
+        let cell = GRID_CELL_SIZE.0 as f32;
+        assert_eq!(board_square_at(left, 0.0), Some((1, 8)));
+        assert_eq!(board_square_at(left + cell * 8.0 - 0.5, cell * 8.0 - 0.5), Some((8, 1)));
+        assert_eq!(board_square_at(left - 0.5, cell), None);
+        assert_eq!(board_square_at(left + cell * 8.0, cell), None);
+        assert_eq!(board_square_at(left, cell * 8.0), None);
+        for file in 1..9 {
+            for rank in 1..9 {
+                let rect = square_rect((file, rank));
+                assert_eq!(board_square_at(rect.x, rect.y), Some((file as u8, rank as u8)));
+            }
+        }
+    }
+
+    #[test]
+    fn the_editor_checks_castling_and_check_and_clears_edited_squares() {
+        let mut state = AppState::headless();
+        state.screen = ScreenState::EditorScreen;
+        state.editor_piece = None;
+        state.shields.insert((8, 1));
+        state.evolving.insert((8, 1));
+        click_square(&mut state, MouseButton::Right, (8, 1));
+        assert!(!state.shields.contains(&(8, 1)) && !state.evolving.contains(&(8, 1)));
+        // the kingside rook is gone but White may still castle there
+        assert!(state.validate_position().is_err());
+        state.castling[0] = false;
+        assert!(state.validate_position().is_ok());
+
+        // White is in check from the rook on a1, yet Black is to move
+        state.board = load_position("4k3/8/8/8/8/8/8/r3K3", Colour::Black);
+        state.castling = [false; 4];
+        assert!(state.validate_position().is_err());
+        state.board.active_color = Colour::White;
+        assert!(state.validate_position().is_ok());
+    }
+
+    #[test]
+    fn every_army_sets_up_a_valid_position() {
+        for army in 0..armies().len() {
+            for col in [Colour::White, Colour::Black].iter() {
+                let mut state = AppState::headless();
+                if *col == Colour::White { state.white_mods.insert(Mods::Army(army)); } else { state.black_mods.insert(Mods::Army(army)); }
+                state.setup_board();
+                assert_eq!(state.validate_position(), Ok(()), "{} army for {:?}", armies()[army].name, col);
+            }
+        }
+    }
+
+    #[test]
+    fn variant_keys_only_change_the_variant() {
+        let mut state = AppState::headless();
+        state.white_mods.insert(Mods::Chess960);
+        state.setup_board();
+        let shuffled = state.board.board.clone();
+        state.toggle_series_variant(Variant::RacingKings);
+        assert_eq!(state.variant, Variant::RacingKings);
+        assert_eq!(state.board.board, load_position(RACING_KINGS_START, Colour::White).board);
+        state.toggle_series_variant(Variant::RacingKings);
+        assert_eq!(state.variant, Variant::Standard);
+        assert_eq!(state.board.board, shuffled);
+        assert!(state.white_mods.contains(&Mods::Chess960));
+
+        // a one-game Duck mod keeps this game a duck game
+        state.white_mods.insert(Mods::Duck);
+        state.setup_board();
+        state.toggle_series_variant(Variant::Antichess);
+        assert_eq!((state.variant, state.series_variant), (Variant::Duck, Variant::Antichess));
+    }
+
+    #[test]
+    fn mate_on_the_partner_board_ends_the_game() {
+        let mut state = AppState::headless();
+        state.bughouse = Some(Bughouse::new(None, false));
+        state.bughouse.as_mut().unwrap().board = load_position("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR", Colour::White);
+        state.step(0.0);
+        assert_eq!(state.screen, ScreenState::ScoreScreen);
+        assert_eq!(state.cur_winner, Some(Colour::White));
+    }
+
+    #[test]
+    fn hosting_waits_for_the_partner_without_blocking() {
+        let link = Link::host("0").unwrap();
+        let port = link.listener.as_ref().unwrap().local_addr().unwrap().port();
+        let mut state = AppState::headless();
+        state.connect(link, false);
+        state.step(1.0);
+        assert!(state.waiting_for_partner());
+        assert_eq!(state.bughouse.as_ref().unwrap().clocks[0].0, BUGHOUSE_CLOCK);
+
+        let _partner = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        for _ in 0..100 {
+            state.step(0.0);
+            if !state.waiting_for_partner() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(!state.waiting_for_partner());
+        assert_eq!(state.bughouse.as_ref().unwrap().link.as_ref().unwrap().status, "");
+    }
+
+    #[test]
+    fn each_bughouse_board_keeps_its_own_history() {
+        let mut state = AppState::headless();
+        state.bughouse = Some(Bughouse::new(None, false));
+        state.promoted.insert((4, 8));
+        state.position_history.push(state.position_fen());
+        state.swap_boards();
+        assert!(state.promoted.is_empty() && state.position_history.is_empty());
+        state.swap_boards();
+        assert!(state.promoted.contains(&(4, 8)));
+        assert_eq!(state.position_history.len(), 1);
+    }
+
+    #[test]
+    fn evolution_carries_on_and_survives_undo_and_saving() {
+        let mut state = AppState::headless();
+        state.board = load_position("4k3/8/2r5/8/1p6/P7/8/4K3", Colour::White);
+        state.white_mods.insert(Mods::Evolution(PieceType::Pawn(Colour::White)));
+        click_square(&mut state, MouseButton::Left, (1, 3));
+        click_square(&mut state, MouseButton::Left, (2, 4));
+        assert_eq!(state.board.board.get(&Position { file: 2, rank: 4 }), Some(&PieceType::Knight(Colour::White)));
+        state.board.active_color = Colour::White;
+        click_square(&mut state, MouseButton::Left, (2, 4));
+        click_square(&mut state, MouseButton::Left, (3, 6));
+        // the knight isn't the Evolution piece any more, but it still evolves
+        assert_eq!(state.board.board.get(&Position { file: 3, rank: 6 }), Some(&PieceType::Bishop(Colour::White)));
+        assert!(state.evolving.contains(&(3, 6)));
+
+        let saved = GameSnapshot::parse(&state.snapshot().to_text()).unwrap();
+        assert_eq!(saved.evolving, state.evolving);
+        assert_eq!(saved.board, state.board.board);
+        assert_eq!(saved.white_mods, state.white_mods);
+        assert_eq!(saved.move_log, state.move_log);
+
+        state.undo();
+        assert_eq!(state.board.board.get(&Position { file: 2, rank: 4 }), Some(&PieceType::Knight(Colour::White)));
+        assert!(state.evolving.contains(&(2, 4)));
+        state.restore(saved);
+        assert_eq!(state.board.board.get(&Position { file: 3, rank: 6 }), Some(&PieceType::Bishop(Colour::White)));
+    }
+
+    #[test]
+    fn fairy_jumps_stop_castling_and_show_in_the_fen() {
+        let mut state = AppState::headless();
+        state.board = load_position("4k3/8/8/8/8/7r/8/4K2R", Colour::White);
+        state.castling = [true, false, false, false];
+        assert!(state.can_castle(Colour::White, true));
+        // a Chancellor on h3 jumps to g1, which the king would cross
+        state.fairies.insert((8, 3), Fairy::Chancellor);
+        assert!(!state.can_castle(Colour::White, true));
+        assert!(state.position_fen().starts_with("4k3/8/8/8/8/7c/8/4K2R "));
+    }
+
+    #[test]
+    fn en_passant_breaks_the_shield_of_the_passed_pawn() {
+        let mut state = AppState::headless();
+        state.board = load_position("4k3/3p4/8/4P3/8/8/8/4K3", Colour::Black);
+        state.shields.insert((4, 7));
+        click_square(&mut state, MouseButton::Left, (4, 7));
+        click_square(&mut state, MouseButton::Left, (4, 5));
+        assert!(state.shields.contains(&(4, 5)));
+        let shielded = state.repetition_key();
+
+        click_square(&mut state, MouseButton::Left, (5, 5));
+        click_square(&mut state, MouseButton::Left, (4, 6));
+        assert_eq!(state.board.board.get(&Position { file: 4, rank: 5 }), Some(&PieceType::Pawn(Colour::Black)));
+        assert_eq!(state.board.board.get(&Position { file: 5, rank: 5 }), Some(&PieceType::Pawn(Colour::White)));
+        assert!(state.shields.is_empty());
+        assert_eq!(state.board.active_color, Colour::Black);
+        // the same placement without the shield is a different position
+        state.board.active_color = Colour::White;
+        assert_ne!(state.repetition_key(), shielded);
+    }
+
+    #[test]
+    fn double_moves_keep_the_history_and_numbering_straight() {
+        let mut state = AppState::headless();
+        state.white_mods.insert(Mods::DoubleMove);
+        state.double_armed = true;
+        click_square(&mut state, MouseButton::Left, (5, 2));
+        click_square(&mut state, MouseButton::Left, (5, 4));
+        assert_eq!(state.board.active_color, Colour::White);
+        assert!(state.position_history.last().unwrap().contains(" w "));
+        click_square(&mut state, MouseButton::Left, (5, 4));
+        click_square(&mut state, MouseButton::Left, (5, 5));
+        assert!(state.position_history.last().unwrap().contains(" b "));
+        assert!(state.game_pgn().contains("1. e4 {double move} -- 2. e5 *"));
+    }
+
+    #[test]
+    fn captures_carry_over_and_sabotage_varies() {
+        let mut state = AppState::headless();
+        state.white_mods.insert(Mods::SpareKnight);
+        state.taken_black_pieces.push(PieceType::Rook(Colour::Black));
+        state.setup_board();
+        assert_eq!(state.taken_black_pieces, vec![PieceType::Rook(Colour::Black), PieceType::Knight(Colour::Black)]);
+        // the unused spare knight is handed out again, not on top of the old one
+        state.setup_board();
+        assert_eq!(state.taken_black_pieces, vec![PieceType::Rook(Colour::Black), PieceType::Knight(Colour::Black)]);
+
+        state.white_mods = [Mods::Sabotage(PieceType::Pawn(Colour::Black))].iter().copied().collect();
+        let mut missing = HashSet::new();
+        for _ in 0..20 {
+            state.setup_board();
+            let pawns = (1..9).filter(|file| state.board.board.get(&Position { file: *file, rank: 7 }) == Some(&PieceType::Pawn(Colour::Black))).collect::<Vec<u8>>();
+            assert_eq!(pawns.len(), 7);
+            missing.insert((1..9).find(|file| !pawns.contains(file)).unwrap());
+        }
+        assert!(missing.len() > 1);
+    }
+
+    #[test]
+    fn teleport_keeps_pawns_off_the_back_rank() {
+        let mut state = AppState::headless();
+        state.board = load_position("4k3/8/8/8/8/8/4P3/3NK3", Colour::White);
+        state.white_mods.insert(Mods::Teleport);
+        assert_eq!(state.teleport_squares(), vec![(4, 1)]);
+    }
+
+    #[test]
+    fn teleport_carries_the_promoted_mark_along() {
+        let mut state = AppState::headless();
+        state.board = load_position("4k3/8/8/8/8/8/8/3QK3", Colour::White);
+        state.white_mods.insert(Mods::Teleport);
+        state.promoted.insert((4, 1));
+        state.teleport((4, 1));
+        assert_eq!(state.board.board.get(&Position { file: 5, rank: 1 }), Some(&PieceType::Queen(Colour::White)));
+        assert_eq!(state.promoted, [(5, 1)].iter().copied().collect::<HashSet<(u8, u8)>>());
+    }
+
+    #[test]
+    fn offers_run_out_instead_of_hanging() {
+        let mut state = AppState::headless();
+        state.white_banished = all_mods(Colour::White).into_iter().collect();
+        assert_eq!(state.roll_mod_for(Colour::White), None);
+        assert!(state.roll_mod_for(Colour::Black).is_some());
+    }
+
+    #[test]
+    fn the_three_offers_differ() {
+        let mut state = AppState::headless();
+        state.cur_winner = Some(Colour::White);
+        for _ in 0..50 {
+            state.roll_mod_offers();
+            let offers = state.random_mods.iter().flatten().collect::<HashSet<&Mods>>();
+            assert_eq!(offers.len(), 3, "{:?}", state.random_mods);
+        }
+    }
+
+    #[test]
+    fn history_lines_read_back() {
+        let record = GameRecord {
+            date: civil_date(19000),
+            series: 1641600000,
+            players: ["Ada".to_string(), "Bo".to_string()],
+            mods: [vec!["Atomic Knight".to_string(), "Chess960".to_string()], Vec::new()],
+            winner: Some(Colour::Black),
+            termination: Termination::Checkmate.name().to_string(),
+            moves: 41,
+        };
+        assert_eq!(record.date, "2022-01-08");
+        assert_eq!(GameRecord::parse(&record.to_line()), Some(record.clone()));
+
+        let rematch = GameRecord { players: ["Bo".to_string(), "Ada".to_string()], winner: None, ..record.clone() };
+        assert_eq!(head_to_head(&[record.clone(), rematch]), vec![("Ada".to_string(), "Bo".to_string(), 0, 1, 1)]);
+        assert_eq!(mod_win_rates(&[record])[0], ("Atomic Knight".to_string(), 0, 1));
+    }
+
+    #[test]
+    fn ratings_move_towards_the_result() {
+        let mut profile = Profile::new("Ada");
+        profile.set = PieceSet::Ruby;
+        profile.side = Colour::Black;
+        assert_eq!(Profile::parse(&profile.to_line()), Some(profile));
+
+        assert_eq!(expected_score(1500.0, 1500.0), 0.5);
+        assert!((expected_score(1600.0, 1400.0) + expected_score(1400.0, 1600.0) - 1.0).abs() < 1e-6);
+        assert!(expected_score(1600.0, 1400.0) > 0.75);
+    }
+
+    #[test]
+    fn flipped_mods_are_the_other_sides_mods() {
+        let black = all_mods(Colour::Black);
+        for modi in all_mods(Colour::White) {
+            assert!(black.contains(&modi.flipped()), "{:?}", modi);
+            assert_eq!(modi.flipped().flipped(), modi);
+        }
+    }
+
+    #[test]
+    fn every_pair_of_mods_plays_its_captures() {
+        let mods = all_mods(Colour::White);
+        // the knight on c3 and the pawn on e4 can both take the pawn on d5
+        let captures = [((3, 3), (4, 5)), ((5, 4), (4, 5))];
+        for (i, a) in mods.iter().enumerate() {
+            for b in mods[i + 1..].iter().filter(|b| !conflicts(a, b)) {
+                for (from, to) in captures.iter() {
+                    let mut state = AppState::headless();
+                    state.white_mods = [*a, *b].iter().copied().collect();
+                    state.setup_board();
+                    // the editor sets up the position and lays out the mods' squares for it
+                    state.screen = ScreenState::EditorScreen;
+                    state.board = load_position("4k3/8/2r5/3p4/4P3/2N5/8/4K3", Colour::White);
+                    state.castling = [false; 4];
+                    state.taken_black_pieces = Vec::new();
+                    state.taken_white_pieces = Vec::new();
+                    state.leave_editor();
+                    let context = format!("{:?} + {:?}, {:?} takes {:?}", a, b, from, to);
+                    assert_eq!(state.screen, ScreenState::GameScreen, "{}: {}", context, state.editor_message);
+
+                    click_square(&mut state, MouseButton::Left, *from);
+                    assert!(state.highlighted_pos.contains(to), "{}", context);
+                    click_square(&mut state, MouseButton::Left, *to);
+                    assert!(state.screen != ScreenState::GameScreen || state.board.active_color == Colour::Black, "{}", context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn declared_conflicts() {
+        let knight = PieceType::Knight(Colour::White);
+        assert!(conflicts(&Mods::Antichess, &Mods::Duck));
+        assert!(conflicts(&Mods::Chess960, &Mods::Army(0)));
+        assert!(conflicts(&Mods::Army(0), &Mods::Army(0)));
+        assert!(!conflicts(&Mods::Army(0), &Mods::Army(1)));
+        assert!(!conflicts(&Mods::Atomic(knight), &Mods::Evolution(knight)));
+        assert!(conflicts(&Mods::Extinction(PieceType::Knight(Colour::Black)), &Mods::CrazyHouse(knight)));
+        assert!(!conflicts(&Mods::Atomic(knight), &Mods::Sniper(knight)));
+        assert!(!conflicts(&Mods::Shield(knight), &Mods::Atomic(knight)));
+    }
+}
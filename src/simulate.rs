@@ -0,0 +1,302 @@
+/**
+ * Headless balance simulator. Bots play modded games through the same `AppState`
+ * the window uses, clicking squares like a player would, and the results are
+ * summed up per mod. Teleports count among a bot's moves, and a bot holding a
+ * double move calls it up now and then, so those mods are played too.
+ */
+
+use super::*;
+use std::fs;
+
+/// Usage shown when the arguments can't be read.
+const USAGE: &str = "usage: simulate [--games N] [--seed N] [--white random|greedy] [--black random|greedy] [--mods N] [--max-plies N] [--json] [--out FILE]";
+
+/// How a simulated side picks its moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bot {
+    /// Any legal move, all equally likely.
+    Random,
+    /// Takes the most valuable piece it can, otherwise plays a random move.
+    Greedy,
+}
+
+impl Bot {
+    fn parse(name: &str) -> Result<Bot, String> {
+        match name {
+            "random" => Ok(Bot::Random),
+            "greedy" => Ok(Bot::Greedy),
+            _ => Err(format!("unknown bot \"{}\"\n{}", name, USAGE)),
+        }
+    }
+}
+
+/// Settings of a simulation run, read from the command line.
+struct Settings {
+    games: usize,
+    seed: u64,
+    /// White's bot and Black's bot.
+    bots: [Bot; 2],
+    /// Mods sampled for each side every game.
+    mods: usize,
+    /// Games still running after this many moves count as draws.
+    max_plies: usize,
+    json: bool,
+    out: Option<String>,
+}
+
+impl Settings {
+    fn parse(args: &[String]) -> Result<Settings, String> {
+        let mut settings = Settings {
+            games: 1000,
+            seed: 0,
+            bots: [Bot::Random, Bot::Random],
+            mods: 2,
+            max_plies: 300,
+            json: false,
+            out: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--json" {
+                settings.json = true;
+                continue;
+            }
+            let value = args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE))?;
+            let number = || value.parse::<u64>().map_err(|_| format!("{} needs a number, not \"{}\"", arg, value));
+            match arg.as_str() {
+                "--games" => settings.games = number()? as usize,
+                "--seed" => settings.seed = number()?,
+                "--white" => settings.bots[0] = Bot::parse(value)?,
+                "--black" => settings.bots[1] = Bot::parse(value)?,
+                "--mods" => settings.mods = number()? as usize,
+                "--max-plies" => settings.max_plies = number()? as usize,
+                "--out" => settings.out = Some(value.clone()),
+                _ => return Err(format!("unknown option \"{}\"\n{}", arg, USAGE)),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Results of the games played with one mod, from the point of view of its holder.
+#[derive(Default)]
+struct ModStats {
+    games: u32,
+    wins: u32,
+    losses: u32,
+    draws: u32,
+    plies: u32,
+}
+
+/// Rough worth of a piece, for the greedy bot.
+fn value(piece: PieceType) -> u32 {
+    match piece {
+        PieceType::Pawn(_) => 1,
+        PieceType::Knight(_) | PieceType::Bishop(_) => 3,
+        PieceType::Rook(_) => 5,
+        PieceType::Queen(_) => 9,
+        PieceType::King(_) => 100,
+    }
+}
+
+/// One in this many turns a bot holding an unspent double move calls it up.
+const DOUBLE_MOVE_ODDS: u32 = 10;
+
+/// Clicks the middle of a board or tray square.
+fn click_square(state: &mut AppState, button: MouseButton, pos: (isize, isize)) {
+    let rect = square_rect(pos);
+    state.click(button, rect.x + rect.w * 0.5, rect.y + rect.h * 0.5);
+}
+
+/// Every move, drop and teleport open to the side to move, as the button and square to click first and the
+/// square to left-click next. They are found by selecting each piece, and right-clicking the king, and reading
+/// off the highlighted squares, just as a player sees them.
+fn actions(state: &mut AppState) -> Vec<(MouseButton, (isize, isize), (isize, isize))> {
+    let col = state.board.active_color;
+    let mut sources = state.board.board.iter()
+        .filter(|(_, piece)| piece.colour() == col)
+        .map(|(pos, _)| (pos.file as isize, pos.rank as isize))
+        .collect::<Vec<(isize, isize)>>();
+    // board order follows the hash map, so sort to keep runs with the same seed the same
+    sources.sort();
+    let (tray, row) = if col == Colour::White { (state.taken_black_pieces.len(), 9) } else { (state.taken_white_pieces.len(), 10) };
+    sources.extend((0..tray).map(|i| (i as isize, row)));
+
+    let king = state.board.board.iter()
+        .find(|(_, piece)| **piece == PieceType::King(col))
+        .map(|(pos, _)| (pos.file as isize, pos.rank as isize));
+
+    let mut actions = Vec::new();
+    let clicks = sources.into_iter().map(|from| (MouseButton::Left, from)).chain(king.map(|king| (MouseButton::Right, king)));
+    for (button, from) in clicks {
+        state.selected_pos = (0, 0);
+        state.highlighted_pos = Vec::new();
+        state.teleporting = false;
+        click_square(state, button, from);
+        // a right-click only selects anything when the king can teleport
+        if button == MouseButton::Right && !state.teleporting {
+            continue;
+        }
+        let mut targets = state.highlighted_pos.clone();
+        targets.sort();
+        actions.extend(targets.into_iter().map(|to| (button, from, to)));
+    }
+    state.selected_pos = (0, 0);
+    state.highlighted_pos = Vec::new();
+    state.teleporting = false;
+    actions
+}
+
+/// Lets `bot` play one move for the side to move, and place the duck afterwards if there is one.
+/// Returns false if it found nothing to play.
+fn play_move(state: &mut AppState, bot: Bot, rng: &mut rngs::StdRng) -> bool {
+    let col = state.board.active_color;
+    if state.double_charge(col) && !state.double_armed && rng.gen_range(0..DOUBLE_MOVE_ODDS) == 0 {
+        state.toggle_double_move(col);
+    }
+    let mut options = actions(state);
+    // every first half of a double move may give check, so call it off again and play a normal move
+    if options.is_empty() && state.double_armed {
+        state.toggle_double_move(col);
+        options = actions(state);
+    }
+    let actions = options;
+    if actions.is_empty() {
+        return false;
+    }
+    let target = |to: &(isize, isize)| if to.1 <= 8 { state.board.board.get(&Position { file: to.0 as u8, rank: to.1 as u8 }).copied() } else { None };
+    let best = match bot {
+        Bot::Random => None,
+        Bot::Greedy => actions.iter()
+            .filter(|(button, _, _)| *button == MouseButton::Left)
+            .filter_map(|(button, from, to)| target(to).filter(|piece| piece.colour() != col).map(|piece| (value(piece), (*button, *from, *to))))
+            .max_by_key(|(worth, _)| *worth)
+            .map(|(_, action)| action),
+    };
+    let (button, from, to) = best.unwrap_or_else(|| actions[rng.gen_range(0..actions.len())]);
+    click_square(state, button, from);
+    click_square(state, MouseButton::Left, to);
+
+    if state.duck_pending {
+        let mut empty = Vec::new();
+        for file in 1..9 {
+            for rank in 1..9 {
+                if !state.board.board.contains_key(&Position { file: file, rank: rank }) && state.duck != Some((file, rank)) {
+                    empty.push((file as isize, rank as isize));
+                }
+            }
+        }
+        if empty.is_empty() {
+            return false;
+        }
+        let square = empty[rng.gen_range(0..empty.len())];
+        click_square(state, MouseButton::Left, square);
+    }
+    true
+}
+
+/// Gives `col` up to `count` more mods, each rolled the way mod offers are, so that none conflicts with a mod in play.
+fn sample_mods(state: &mut AppState, col: Colour, count: usize) {
+    for _ in 0..count {
+        let modi = match state.roll_mod_for(col) {
+            Some(modi) => modi,
+            None => return,
+        };
+        if col == Colour::White {
+            state.white_mods.insert(modi);
+        } else {
+            state.black_mods.insert(modi);
+        }
+    }
+}
+
+/// Plays one game between the bots with the mods the sides hold. Returns the winner, if any, and the number of moves played.
+fn play_game(state: &mut AppState, settings: &Settings, rng: &mut rngs::StdRng) -> (Option<Colour>, usize) {
+    state.screen = ScreenState::GameScreen;
+    state.setup_board();
+
+    while state.screen == ScreenState::GameScreen && state.move_log.len() < settings.max_plies {
+        let bot = settings.bots[if state.board.active_color == Colour::White { 0 } else { 1 }];
+        if !play_move(state, bot, rng) {
+            break;
+        }
+        state.step(1.0);
+    }
+    let winner = if state.screen == ScreenState::ScoreScreen { state.cur_winner } else { None };
+    (winner, state.move_log.len())
+}
+
+/// Per-mod results as CSV, one mod per line.
+fn to_csv(stats: &[(String, ModStats)]) -> String {
+    let mut csv = String::from("mod,games,wins,losses,draws,win_rate,draw_rate,average_moves\n");
+    for (name, stat) in stats.iter() {
+        let games = stat.games.max(1) as f32;
+        csv.push_str(&format!("\"{}\",{},{},{},{},{:.3},{:.3},{:.1}\n", name, stat.games, stat.wins, stat.losses, stat.draws,
+            stat.wins as f32 / games, stat.draws as f32 / games, stat.plies as f32 / games));
+    }
+    csv
+}
+
+/// Per-mod results as a JSON array.
+fn to_json(stats: &[(String, ModStats)]) -> String {
+    let rows = stats.iter().map(|(name, stat)| {
+        let games = stat.games.max(1) as f32;
+        format!("  {{\"mod\": \"{}\", \"games\": {}, \"wins\": {}, \"losses\": {}, \"draws\": {}, \"win_rate\": {:.3}, \"draw_rate\": {:.3}, \"average_moves\": {:.1}}}",
+            name.replace('\\', "\\\\").replace('"', "\\\""), stat.games, stat.wins, stat.losses, stat.draws,
+            stat.wins as f32 / games, stat.draws as f32 / games, stat.plies as f32 / games)
+    }).collect::<Vec<String>>();
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
+
+/// Runs a simulation with the given command line arguments and writes the per-mod results.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let settings = Settings::parse(args)?;
+    // the run seed drives both the mods sampled here and every roll the rules make
+    unsafe { seed = settings.seed; }
+    let mut rng: rngs::StdRng = rand::SeedableRng::seed_from_u64(settings.seed);
+
+    let mut stats: HashMap<String, ModStats> = HashMap::new();
+    let mut results = (0, 0, 0);
+    for game in 0..settings.games {
+        // a fresh state per game, so trays, scores and spent rerolls don't carry over from the last one
+        let mut state = AppState::headless();
+        sample_mods(&mut state, Colour::White, settings.mods);
+        sample_mods(&mut state, Colour::Black, settings.mods);
+        // setting up the board uses up the variant mods, so keep a copy for the report
+        let mods = [state.white_mods.clone(), state.black_mods.clone()];
+        let (winner, plies) = play_game(&mut state, &settings, &mut rng);
+        match winner {
+            Some(Colour::White) => results.0 += 1,
+            Some(Colour::Black) => results.1 += 1,
+            None => results.2 += 1,
+        }
+
+        for (col, held) in [Colour::White, Colour::Black].iter().zip(mods.iter()) {
+            for modi in held.iter() {
+                let stat = stats.entry(modi.display_name(*col)).or_insert_with(ModStats::default);
+                stat.games += 1;
+                stat.plies += plies as u32;
+                match winner {
+                    Some(winner) if winner == *col => stat.wins += 1,
+                    Some(_) => stat.losses += 1,
+                    None => stat.draws += 1,
+                }
+            }
+        }
+        if (game + 1) % 100 == 0 {
+            eprintln!("{}/{} games played", game + 1, settings.games);
+        }
+    }
+    eprintln!("White won {}, Black won {}, {} drawn", results.0, results.1, results.2);
+
+    let mut stats = stats.into_iter().collect::<Vec<(String, ModStats)>>();
+    stats.sort_by(|a, b| a.0.cmp(&b.0));
+    let report = if settings.json { to_json(&stats) } else { to_csv(&stats) };
+    match &settings.out {
+        Some(path) => fs::write(path, report).map_err(|error| format!("couldn't write {}: {}", path, error)),
+        None => {
+            print!("{}", report);
+            Ok(())
+        },
+    }
+}